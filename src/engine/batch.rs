@@ -0,0 +1,138 @@
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::add_step_internal_error;
+use crate::create_internal_error;
+use crate::display_debug;
+use crate::display_debug_block;
+use crate::engine::Environment;
+use crate::engine::Renderer;
+use crate::utils::conf::Configuration;
+use crate::utils::error::InternalError;
+use crate::utils::glob;
+
+#[derive(Debug, Default)]
+pub struct BatchReport {
+  pub rendered: Vec<PathBuf>,
+  pub copied: Vec<PathBuf>,
+}
+
+pub fn is_template(conf: &Configuration, relative_path: &str) -> bool {
+  (conf.includes.is_empty()
+    || conf
+      .includes
+      .iter()
+      .any(|p| glob::matches(p, relative_path)))
+    && !conf
+      .excludes
+      .iter()
+      .any(|p| glob::matches(p, relative_path))
+}
+
+pub fn render_directory(
+  conf: &Configuration,
+  source: &Path,
+  destination: &Path,
+) -> Result<BatchReport, InternalError> {
+  if !source.is_dir() {
+    return Err(create_internal_error!(
+      "The source of the batch rendering is not a directory",
+      format!("Found '{}' path", source.display())
+    ));
+  }
+  let mut files: Vec<PathBuf> = vec![];
+  let skipped = destination.canonicalize().ok();
+  walk(source, skipped.as_deref(), &mut files)?;
+  let mut report = BatchReport::default();
+  for file in files {
+    let relative = file.strip_prefix(source).unwrap_or(&file);
+    let relative_path = relative
+      .components()
+      .map(|c| c.as_os_str().to_string_lossy())
+      .collect::<Vec<_>>()
+      .join("/");
+    let target = destination.join(relative);
+    if let Some(parent) = target.parent() {
+      if let Err(err) = fs::create_dir_all(parent) {
+        return Err(create_internal_error!(
+          "An error occurred during creating the output directory",
+          format!("Found '{}' path", parent.display()),
+          format!("Error details = {}", err)
+        ));
+      }
+    }
+    if is_template(conf, &relative_path) {
+      display_debug_block!(conf, "Batch rendering", "Render '{}'", relative_path);
+      let mut renderer = Renderer::new(conf, Environment::from_args(conf));
+      let output = match renderer.render_path(&file) {
+        Ok(o) => o,
+        Err(mut err) => {
+          return Err(add_step_internal_error!(
+            err,
+            format!("Error during rendering '{}'", file.display())
+          ))
+        }
+      };
+      if let Err(err) = fs::write(&target, output) {
+        return Err(create_internal_error!(
+          "An error occurred during writing a rendered file",
+          format!("Found '{}' path", target.display()),
+          format!("Error details = {}", err)
+        ));
+      }
+      report.rendered.push(target);
+    } else {
+      display_debug_block!(conf, "Batch rendering", "Copy '{}'", relative_path);
+      if let Err(err) = fs::copy(&file, &target) {
+        return Err(create_internal_error!(
+          "An error occurred during copying a file",
+          format!("Found '{}' path", file.display()),
+          format!("Error details = {}", err)
+        ));
+      }
+      report.copied.push(target);
+    }
+  }
+  Ok(report)
+}
+
+fn walk(
+  directory: &Path,
+  skipped: Option<&Path>,
+  files: &mut Vec<PathBuf>,
+) -> Result<(), InternalError> {
+  let items = match fs::read_dir(directory) {
+    Ok(items) => items,
+    Err(err) => {
+      return Err(create_internal_error!(
+        format!("Error during reading directory '{}'", directory.display()),
+        format!("Result of read_dir = '{}'", err)
+      ))
+    }
+  };
+  let mut paths: Vec<PathBuf> = vec![];
+  for item in items {
+    match item {
+      Ok(directory_item) => paths.push(directory_item.path()),
+      Err(err) => {
+        return Err(create_internal_error!(
+          format!("Error during reading directory '{}'", directory.display()),
+          format!("Result of read_dir = '{}'", err)
+        ))
+      }
+    }
+  }
+  paths.sort();
+  for path in paths {
+    if path.is_dir() {
+      if skipped.is_some() && path.canonicalize().ok().as_deref() == skipped {
+        continue;
+      }
+      walk(&path, skipped, files)?;
+    } else if path.is_file() {
+      files.push(path);
+    }
+  }
+  Ok(())
+}
@@ -1,19 +1,49 @@
-use std::fs;
-
 use crate::create_internal_error;
-use crate::display_debug;
-use crate::display_debug_block;
 use crate::engine::environment;
 use crate::engine::resolver;
 use crate::engine::Environment;
 use crate::utils::conf::Configuration;
+use crate::utils::conf::Delimiters;
 use crate::utils::error::InternalError;
+use crate::utils::error::SourceLocation;
 
 #[derive(Debug)]
 pub struct Document<'c> {
   pub conf: &'c Configuration,
   pub source: String,
   pub stack: Vec<Part>,
+  pub tree: Vec<Node>,
+  pub delimiters: Delimiters,
+  pub path: Option<String>,
+  pub reentrance: usize,
+  pub reentrant: bool,
+  // the source read by the first pass and, for each span of the current
+  // source, where it comes from in it
+  original: String,
+  origins: Vec<Origin>,
+  // for each part of the stack, the span of the node producing it
+  producers: Vec<(usize, usize)>,
+}
+
+// a span of the current source, copied from the original source (position by
+// position) or generated by the part at the original span
+#[derive(Debug, Clone)]
+struct Origin {
+  start: usize,
+  end: usize,
+  original: (usize, usize),
+  copied: bool,
+}
+
+impl Origin {
+  fn of_source(source: &str) -> Vec<Origin> {
+    vec![Origin {
+      start: 0,
+      end: source.len(),
+      original: (0, source.len()),
+      copied: true,
+    }]
+  }
 }
 
 #[allow(dead_code)]
@@ -21,15 +51,31 @@ impl<'c> Document<'c> {
   pub fn new(conf: &'c Configuration, source: String) -> Self {
     Document {
       conf,
+      original: source.clone(),
+      origins: Origin::of_source(&source),
+      producers: vec![],
       source,
       stack: vec![],
+      tree: vec![],
+      delimiters: conf.delimiters.clone(),
+      path: None,
+      reentrance: 0,
+      reentrant: false,
     }
   }
   pub fn from_str<'a>(conf: &'c Configuration, source: &'a str) -> Self {
     Document {
       conf,
       source: source.to_string(),
+      original: source.to_string(),
+      origins: Origin::of_source(source),
+      producers: vec![],
       stack: vec![],
+      tree: vec![],
+      delimiters: conf.delimiters.clone(),
+      path: None,
+      reentrance: 0,
+      reentrant: false,
     }
   }
   pub fn stack_len(&self) -> (usize, usize) {
@@ -43,158 +89,426 @@ impl<'c> Document<'c> {
     }
     (i + y, y)
   }
+  // the location in the original source of what produced the part
+  pub fn locate(&self, start: usize, end: usize) -> SourceLocation {
+    match self.original_span(start, end) {
+      Some((s, e)) => {
+        SourceLocation::from_source(self.path.clone(), self.reentrance, &self.original, s, e)
+      }
+      None => {
+        SourceLocation::from_source(self.path.clone(), self.reentrance, &self.source, start, end)
+      }
+    }
+  }
+  fn original_span(&self, start: usize, end: usize) -> Option<(usize, usize)> {
+    let origin = self
+      .origins
+      .iter()
+      .find(|o| o.start <= start && start < o.end)
+      .or_else(|| self.origins.last().filter(|o| o.end == start))?;
+    match origin.copied {
+      true => {
+        let s = origin.original.0 + start - origin.start;
+        Some((s, s + end.min(origin.end).max(start) - start))
+      }
+      false => Some(origin.original),
+    }
+  }
+  // the origins of the span start..end of the current source, copied at the
+  // position 'at' of the next source
+  fn copied_origins(&self, start: usize, end: usize, at: usize) -> Vec<Origin> {
+    self
+      .origins
+      .iter()
+      .filter(|o| o.start < end && start < o.end)
+      .map(|o| {
+        let (from, to) = (start.max(o.start), end.min(o.end));
+        Origin {
+          start: at + from - start,
+          end: at + to - start,
+          original: match o.copied {
+            true => (o.original.0 + from - o.start, o.original.0 + to - o.start),
+            false => o.original,
+          },
+          copied: o.copied,
+        }
+      })
+      .collect()
+  }
   pub fn stack_get(&self, position: usize) -> Option<&Part> {
     self.stack.get(position)
   }
+  pub fn expression_source(&self, start: usize, end: usize) -> &str {
+    let (opening, ending) = &self.delimiters.expression;
+    strip_markers(
+      &self.source[start + opening.len()..end - ending.len()],
+      &[MARKER],
+    )
+  }
+  pub fn statement_source(&self, start: usize, end: usize) -> &str {
+    let (opening, ending) = &self.delimiters.statement;
+    strip_markers(
+      &self.source[start + opening.len()..end - ending.len()],
+      &[MARKER, KEEP],
+    )
+  }
+  pub fn apply_pragma(&mut self) -> Result<(), InternalError> {
+    let first_line = match self.source.split_once('\n') {
+      Some((line, _)) => line,
+      None => &self.source[..],
+    };
+    let pragma = match first_line
+      .trim_end()
+      .strip_prefix("{#")
+      .and_then(|l| l.strip_suffix("#}"))
+    {
+      Some(p) => p.trim(),
+      None => return Ok(()),
+    };
+    let value = match pragma.strip_prefix("delimiters") {
+      Some(v) if v.starts_with(char::is_whitespace) => v,
+      _ => return Ok(()),
+    };
+    match Delimiters::parse(value) {
+      Ok(d) => self.delimiters = d,
+      Err(err) => {
+        let mut err = create_internal_error!("Invalid delimiters pragma on the first line", err);
+        err.set_location(self.locate(0, first_line.len()));
+        return Err(err);
+      }
+    }
+    self.source = match self.source.split_once('\n') {
+      Some((_, rest)) => rest.to_string(),
+      None => "".to_string(),
+    };
+    self.original = self.source.clone();
+    self.origins = Origin::of_source(&self.source);
+    Ok(())
+  }
+  fn find_opening(&self, rest: &str) -> Option<(Part, usize)> {
+    let mut found: Option<(Part, usize)> = None;
+    for (part, opening) in [
+      (Part::Expression(0, 0), &self.delimiters.expression.0),
+      (Part::Statement(0, 0), &self.delimiters.statement.0),
+      (Part::Comment(0, 0), &self.delimiters.comment.0),
+    ] {
+      if rest.starts_with(&opening[..]) {
+        match found {
+          Some((_, l)) if l >= opening.len() => (),
+          _ => found = Some((part, opening.len())),
+        }
+      }
+    }
+    found
+  }
+  fn find_ending(&self, rest: &str) -> Option<(Part, usize)> {
+    for (part, ending) in [
+      (Part::Expression(0, 0), &self.delimiters.expression.1),
+      (Part::Statement(0, 0), &self.delimiters.statement.1),
+      (Part::Comment(0, 0), &self.delimiters.comment.1),
+    ] {
+      if rest.starts_with(&ending[..]) {
+        return Some((part, ending.len()));
+      }
+    }
+    None
+  }
   pub fn parse_parts(&mut self) -> Result<bool, InternalError> {
-    let iter = self.source.char_indices().collect::<Vec<(usize, char)>>();
-    if iter.len() == 0 {
+    if self.source.is_empty() {
       return Ok(false);
     }
     let mut part_type: Part = Part::StaticText(0, 0);
-    let mut max = 0;
-    for window in iter.windows(2) {
-      if let [(ref_i, w1), (_, w2)] = window {
-        let i: usize = *ref_i;
-        max = i;
-        match (w1, w2) {
-          ('{', '{') => {
-            match part_type {
-              Part::StaticText(y, _) => {
-                if y < i {
-                  self.stack.push(Part::StaticText(y, i))
-                }
-              }
-              p => {
-                return Err(create_internal_error!(format!(
-                  "not authorized : start another part 'Expression' in {:?} part",
-                  p
-                )));
-              }
-            }
-            part_type = Part::Expression(i, 0);
-          }
-          ('}', '}') => {
-            match part_type {
-              Part::Expression(y, _) => self.stack.push(Part::Expression(y, i + 2)),
-              p => {
-                return Err(create_internal_error!(format!(
-                  "not authorized : end another part 'Expression' in {:?} part",
-                  p
-                )));
-              }
+    let mut i: usize = 0;
+    let mut is_text: bool = false;
+    let mut is_escaping: bool = false;
+    let l = self.source.len();
+    while i < l {
+      let rest = &self.source[i..];
+      let c = rest.chars().next().map_or(' ', |c| c);
+      if is_text {
+        // delimiters inside a text literal ("...") of an expression or a
+        // statement are part of the text, as in the parser
+        if is_escaping {
+          is_escaping = false;
+        } else if c == '\\' {
+          is_escaping = true;
+        } else if c == '"' {
+          is_text = false;
+        }
+        i += c.len_utf8();
+        continue;
+      }
+      let ending = match part_type {
+        Part::Expression(_, _) => Some(&self.delimiters.expression.1),
+        Part::Statement(_, _) => Some(&self.delimiters.statement.1),
+        Part::Comment(_, _) => Some(&self.delimiters.comment.1),
+        _ => None,
+      };
+      if let Some(ending) = ending {
+        if rest.starts_with(&ending[..]) {
+          let e = i + ending.len();
+          let (y, opening, is_block) = match part_type {
+            Part::Expression(y, _) => (y, self.delimiters.expression.0.len(), false),
+            Part::Statement(y, _) => (y, self.delimiters.statement.0.len(), true),
+            Part::Comment(y, _) => (y, self.delimiters.comment.0.len(), true),
+            _ => (i, 0, false),
+          };
+          self.stack.push(match part_type {
+            Part::Expression(y, _) => Part::Expression(y, e),
+            Part::Statement(y, _) => Part::Statement(y, e),
+            Part::Comment(y, _) => Part::Comment(y, e),
+            p => p,
+          });
+          let following = &self.source[e..];
+          let mut next = e;
+          if i > y + opening && self.source[..i].ends_with(MARKER) {
+            next += following.len() - following.trim_start().len();
+          } else if is_block
+            && self.conf.trim_blocks
+            && !(i > y + opening && self.source[..i].ends_with(KEEP))
+          {
+            if following.starts_with("\r\n") {
+              next += 2;
+            } else if following.starts_with('\n') {
+              next += 1;
             }
-            part_type = Part::StaticText(i + 2, 0);
           }
-          ('{', '%') => {
-            match part_type {
-              Part::StaticText(y, _) => {
-                if y < i {
-                  self.stack.push(Part::StaticText(y, i))
+          part_type = Part::StaticText(next, 0);
+          i = next;
+          continue;
+        }
+      }
+      if c == '"' && matches!(part_type, Part::Expression(_, _) | Part::Statement(_, _)) {
+        is_text = true;
+        i += 1;
+        continue;
+      }
+      if let Some((part, length)) = self.find_opening(rest) {
+        match part_type {
+          Part::StaticText(y, _) => {
+            let preceding = &self.source[y..i];
+            let mut t = i;
+            if rest[length..].starts_with(MARKER) {
+              t = y + preceding.trim_end().len();
+            } else if self.conf.lstrip_blocks
+              && !matches!(part, Part::Expression(_, _))
+              && !rest[length..].starts_with(KEEP)
+            {
+              let line_start = match preceding.rfind('\n') {
+                Some(p) => Some(y + p + 1),
+                None if y == 0 || self.source[..y].ends_with('\n') => Some(y),
+                None => None,
+              };
+              if let Some(line_start) = line_start {
+                if self.source[line_start..i]
+                  .chars()
+                  .all(|c| c == ' ' || c == '\t')
+                {
+                  t = line_start;
                 }
               }
-              p => {
-                return Err(create_internal_error!(format!(
-                  "not authorized : start another part 'Statement' in {:?} part",
-                  p
-                )));
-              }
             }
-            part_type = Part::Statement(i, 0);
-          }
-          ('%', '}') => {
-            match part_type {
-              Part::Statement(y, _) => self.stack.push(Part::Statement(y, i + 2)),
-              p => {
-                return Err(create_internal_error!(format!(
-                  "not authorized : end another part 'Statement' in {:?} part",
-                  p
-                )));
-              }
-            }
-            part_type = Part::StaticText(i + 2, 0);
-          }
-          ('{', '#') => {
-            match part_type {
-              Part::StaticText(y, _) => {
-                if y < i {
-                  self.stack.push(Part::StaticText(y, i))
-                }
-              }
-              p => {
-                return Err(create_internal_error!(format!(
-                  "not authorized : start another part 'Comment' in {:?} part",
-                  p
-                )));
-              }
+            if y < t {
+              self.stack.push(Part::StaticText(y, t))
             }
-            part_type = Part::Comment(i, 0);
           }
-          ('#', '}') => {
-            match part_type {
-              Part::Comment(y, _) => self.stack.push(Part::Comment(y, i + 2)),
-              p => {
-                return Err(create_internal_error!(format!(
-                  "not authorized : end another part 'Comment' in {:?} part",
-                  p
-                )));
-              }
-            }
-            part_type = Part::StaticText(i + 2, 0);
+          p => {
+            let mut err = create_internal_error!(format!(
+              "not authorized : start another part '{}' in {:?} part",
+              part.name(),
+              p
+            ));
+            err.set_location(self.locate(i, i + length));
+            return Err(err);
           }
-          _ => (),
         }
+        part_type = match part {
+          Part::Expression(_, _) => Part::Expression(i, 0),
+          Part::Statement(_, _) => Part::Statement(i, 0),
+          _ => Part::Comment(i, 0),
+        };
+        i += length;
+        continue;
+      }
+      if let Some((part, length)) = self.find_ending(rest) {
+        let mut err = create_internal_error!(format!(
+          "not authorized : end another part '{}' in {:?} part",
+          part.name(),
+          part_type
+        ));
+        err.set_location(self.locate(i, i + length));
+        return Err(err);
       }
+      i += c.len_utf8();
     }
-    let l = self.source.len();
     match part_type {
       Part::StaticText(s, _) => {
-        if max < l && s < l {
+        if s < l {
           self.stack.push(Part::StaticText(s, l));
         }
       }
       Part::Statement(s, _) => {
-        return Err(create_internal_error!(format!(
-          "no ending for expression (start at {:?})",
-          s
-        )))
+        let mut err =
+          create_internal_error!(format!("no ending for expression (start at {:?})", s));
+        err.set_location(self.locate(s, s + self.delimiters.statement.0.len()));
+        return Err(err);
       }
       Part::Expression(s, _) => {
-        return Err(create_internal_error!(format!(
-          "no ending for expression (start at {:?})",
-          s
-        )))
+        let mut err =
+          create_internal_error!(format!("no ending for expression (start at {:?})", s));
+        err.set_location(self.locate(s, s + self.delimiters.expression.0.len()));
+        return Err(err);
       }
       Part::Comment(s, _) => {
-        return Err(create_internal_error!(format!(
-          "no ending for comment (start at {:?})",
-          s
-        )))
+        let mut err = create_internal_error!(format!("no ending for comment (start at {:?})", s));
+        err.set_location(self.locate(s, s + self.delimiters.comment.0.len()));
+        return Err(err);
       }
       _ => (),
     }
+    self.tree = self.build_tree()?;
     Ok(true)
   }
+  pub fn keyword(&self, position: usize) -> Option<&str> {
+    match self.stack.get(position) {
+      Some(&Part::Statement(s, e)) => {
+        let content = self.statement_source(s, e).trim_start();
+        let l = content
+          .find(|c: char| !c.is_alphanumeric() && c != '_')
+          .unwrap_or(content.len());
+        Some(&content[..l])
+      }
+      _ => None,
+    }
+  }
+  pub fn parts(&self, nodes: &[Node]) -> Vec<Part> {
+    let mut parts: Vec<Part> = vec![];
+    for node in nodes {
+      match node {
+        &Node::Unit(position) => parts.extend(self.stack_get(position).cloned()),
+        Node::Bordered(opening, children, ending) => {
+          parts.extend(self.stack_get(*opening).cloned());
+          parts.extend(self.parts(children));
+          parts.extend(self.stack_get(*ending).cloned());
+        }
+      }
+    }
+    parts
+  }
+  fn build_tree(&self) -> Result<Vec<Node>, InternalError> {
+    let mut levels: Vec<(usize, &str, Vec<Node>)> = vec![(0, "", vec![])];
+    for position in 0..self.stack.len() {
+      let keyword = self.keyword(position).unwrap_or("");
+      let in_raw = levels.last().is_some_and(|(_, k, _)| *k == "raw");
+      if BORDERED.iter().any(|(opening, _)| *opening == keyword) && (!in_raw || keyword == "raw") {
+        levels.push((position, keyword, vec![]));
+        continue;
+      }
+      match BORDERED.iter().find(|(_, ending)| *ending == keyword) {
+        Some((opening, _)) if !in_raw || keyword == "endraw" => {
+          if levels.len() < 2 {
+            let mut err = create_internal_error!(format!(
+              "unexpected '{}' : no '{}' opened before",
+              keyword, opening
+            ));
+            err.set_location(self.locate_part(position));
+            return Err(err);
+          }
+          let (start, current, children) = levels.pop().unwrap();
+          if current != *opening {
+            let mut err = create_internal_error!(
+              format!(
+                "unexpected '{}' : '{}' opened at line {} is not closed",
+                keyword,
+                current,
+                self.locate_part(start).line
+              ),
+              format!("expected '{}'", ending_of(current))
+            );
+            err.set_location(self.locate_part(position));
+            return Err(err);
+          }
+          levels
+            .last_mut()
+            .unwrap()
+            .2
+            .push(Node::Bordered(start, children, position));
+        }
+        _ => levels.last_mut().unwrap().2.push(Node::Unit(position)),
+      }
+    }
+    if levels.len() > 1 {
+      let (start, current, _) = levels.pop().unwrap();
+      let location = self.locate_part(start);
+      let mut err = create_internal_error!(
+        format!("unclosed '{}' opened at line {}", current, location.line),
+        format!("expected '{}'", ending_of(current))
+      );
+      err.set_location(location);
+      return Err(err);
+    }
+    Ok(levels.pop().map_or(vec![], |(_, _, children)| children))
+  }
+  fn locate_part(&self, position: usize) -> SourceLocation {
+    match self.stack.get(position) {
+      Some(&Part::Statement(s, e)) => self.locate(s, e),
+      _ => self.locate(0, 0),
+    }
+  }
   pub fn transform(&mut self, env: &mut Environment) {
     env.transform(self);
+    let (destination, origins) = self.assemble(&self.stack, &self.producers);
+    self.stack = vec![];
+    self.tree = vec![];
+    self.producers = vec![];
+    self.origins = origins;
+    self.source = destination;
+  }
+  // the text of the parts and its origins, the generated texts coming from
+  // the span of their producer
+  fn assemble(&self, parts: &[Part], producers: &[(usize, usize)]) -> (String, Vec<Origin>) {
     let mut destination: String = "".to_string();
-    for p in &self.stack {
+    let mut origins: Vec<Origin> = vec![];
+    for (i, p) in parts.iter().enumerate() {
+      let at = destination.len();
       match p {
-        &Part::StaticText(s, e) => destination.push_str(&self.source[s..e]),
-        Part::GeneratedText(s) => destination.push_str(&s[..]),
-        &Part::Statement(s, e) => destination.push_str(&self.source[s..e]),
-        &Part::Expression(s, e) => destination.push_str(&self.source[s..e]),
+        &Part::StaticText(s, e) | &Part::Statement(s, e) | &Part::Expression(s, e) => {
+          destination.push_str(&self.source[s..e]);
+          origins.extend(self.copied_origins(s, e, at));
+        }
+        Part::GeneratedText(s) => {
+          destination.push_str(&s[..]);
+          let (s, e) = producers.get(i).copied().unwrap_or((0, 0));
+          if let Some(original) = self.original_span(s, e) {
+            origins.push(Origin {
+              start: at,
+              end: destination.len(),
+              original,
+              copied: false,
+            });
+          }
+        }
         Part::Comment(_, _) => (),
       }
     }
-    self.stack = vec![];
-    self.source = destination;
+    (destination, origins)
+  }
+  // a document of the parts, located in the original source of this one
+  pub fn nested(&self, parts: &[Part], producer: (usize, usize)) -> Document<'c> {
+    let (source, origins) = self.assemble(parts, &vec![producer; parts.len()]);
+    let mut doc = Document::new(self.conf, source);
+    doc.original = self.original.clone();
+    doc.origins = origins;
+    doc.delimiters = self.delimiters.clone();
+    doc.path = self.path.clone();
+    doc
   }
   pub fn resolve(&mut self, env: &mut environment::Environment) -> Result<bool, InternalError> {
     match resolver::resolve(self, env) {
       Ok(r) => {
         if r.changed {
           self.stack = r.stack;
+          self.producers = r.producers;
           Ok(true)
         } else {
           Ok(false)
@@ -203,19 +517,40 @@ impl<'c> Document<'c> {
       Err(err) => Err(err),
     }
   }
-  pub fn write<'a>(&self) -> Option<String> {
-    match &self.conf.output {
-      Some(path) => {
-        display_debug_block!(self.conf, "Try to write output", "Path = {:?}", path);
-        match fs::write(path, &self.source) {
-          Ok(_) => (),
-          Err(err) => return Some(err.to_string()),
-        }
-      }
-      None => print!("{}", self.source),
-    }
-    None
-  }
+}
+
+pub const BORDERED: [(&str, &str); 7] = [
+  ("if", "endif"),
+  ("for", "endfor"),
+  ("block", "endblock"),
+  ("raw", "endraw"),
+  ("with", "endwith"),
+  ("capture", "endcapture"),
+  ("autoescape", "endautoescape"),
+];
+
+fn ending_of(opening: &str) -> &str {
+  BORDERED
+    .iter()
+    .find(|(o, _)| *o == opening)
+    .map_or("", |(_, e)| e)
+}
+
+#[derive(Debug, Clone)]
+pub enum Node {
+  Unit(usize),
+  Bordered(usize, Vec<Node>, usize),
+}
+
+// '{{-' trims the whitespace before the part, '-}}' the one after it
+const MARKER: char = '-';
+// '{%+' keeps the whitespace before the part, '+%}' the one after it (despite
+// --lstrip-blocks and --trim-blocks)
+const KEEP: char = '+';
+
+fn strip_markers<'a>(content: &'a str, markers: &[char]) -> &'a str {
+  let content = content.strip_prefix(markers).unwrap_or(content);
+  content.strip_suffix(markers).unwrap_or(content)
 }
 
 #[derive(Debug, Clone)]
@@ -226,3 +561,15 @@ pub enum Part {
   Expression(usize, usize),
   Comment(usize, usize),
 }
+
+impl Part {
+  pub fn name(&self) -> &'static str {
+    match self {
+      Part::StaticText(_, _) => "StaticText",
+      Part::GeneratedText(_) => "GeneratedText",
+      Part::Statement(_, _) => "Statement",
+      Part::Expression(_, _) => "Expression",
+      Part::Comment(_, _) => "Comment",
+    }
+  }
+}
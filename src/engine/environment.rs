@@ -1,53 +1,223 @@
+use std::collections::BTreeMap;
 use std::collections::HashMap;
+use std::env;
+use std::fmt;
 
 use crate::engine::document::Part;
+use crate::engine::extensions::Value;
 use crate::engine::Document;
 use crate::utils::conf::Configuration;
+use crate::utils::conf::Escaping;
+use crate::utils::conf::UndefinedPolicy;
 
-#[derive(Debug)]
+pub const ENV_NAMESPACE: &str = "env.";
+
+#[derive(Default, Clone)]
+pub struct ProcessVariables(HashMap<String, Value>);
+
+impl fmt::Debug for ProcessVariables {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "ProcessVariables({} hidden)", self.0.len())
+  }
+}
+
+#[derive(Debug, Clone)]
 pub struct Environment {
-  stack: HashMap<String, String>,
+  scopes: Vec<HashMap<String, Value>>,
+  process: ProcessVariables,
+  constants: HashMap<String, String>,
   blocks: HashMap<String, Vec<Part>>,
+  dependencies: Vec<String>,
+  undefined: UndefinedPolicy,
+  escaping: Escaping,
+}
+
+impl Default for Environment {
+  fn default() -> Self {
+    Environment::new()
+  }
 }
 
 #[allow(dead_code)]
 impl Environment {
   pub fn new() -> Self {
     Environment {
-      stack: HashMap::new(),
+      scopes: vec![HashMap::new()],
+      process: ProcessVariables::default(),
+      constants: HashMap::new(),
       blocks: HashMap::new(),
+      dependencies: vec![],
+      undefined: UndefinedPolicy::Error,
+      escaping: Escaping::None,
     }
   }
   pub fn from_args(conf: &Configuration) -> Self {
     Environment {
-      stack: conf.variables.clone(),
+      scopes: vec![conf.variables.clone()],
+      process: match conf.env_prefix {
+        Some(ref prefix) => ProcessVariables(
+          env::vars()
+            .filter(|(k, _)| k.starts_with(prefix))
+            .map(|(k, v)| (k, Value::Text(v)))
+            .collect(),
+        ),
+        None => ProcessVariables::default(),
+      },
+      constants: match conf.lock_vars {
+        true => conf
+          .variables
+          .keys()
+          .map(|k| {
+            (
+              k.to_string(),
+              "on the command line, locked with --lock-vars".to_string(),
+            )
+          })
+          .collect(),
+        false => HashMap::new(),
+      },
       blocks: HashMap::new(),
+      dependencies: vec![],
+      undefined: conf.undefined.clone(),
+      escaping: conf.autoescape.clone(),
     }
   }
-  pub fn set(&mut self, key: String, value: String) -> Option<String> {
-    if key.starts_with("$") {
-      match self.stack.get(&key) {
-        Some(real_key) => self.stack.insert(real_key.to_string(), value),
-        None => return Some(format!("invalid indirection key ('{}' not found)", key)),
+  pub fn undefined_policy(&self) -> &UndefinedPolicy {
+    &self.undefined
+  }
+  pub fn escaping(&self) -> &Escaping {
+    &self.escaping
+  }
+  pub fn set_escaping(&mut self, escaping: Escaping) -> Escaping {
+    std::mem::replace(&mut self.escaping, escaping)
+  }
+  fn scoped(&self, key: &str) -> Option<&Value> {
+    self.scopes.iter().rev().find_map(|scope| scope.get(key))
+  }
+  fn lookup(&self, key: &str) -> Option<&Value> {
+    if let Some(name) = key.strip_prefix(ENV_NAMESPACE) {
+      return self.process.0.get(name);
+    }
+    if let Some(value) = self.scoped(key) {
+      return Some(value);
+    }
+    let start = key.find(['.', '['])?;
+    let mut value = self.scoped(&key[..start])?;
+    let mut rest = &key[start..];
+    while !rest.is_empty() {
+      let member: String;
+      if let Some(r) = rest.strip_prefix('.') {
+        let end = r.find(['.', '[']).unwrap_or(r.len());
+        member = r[..end].to_string();
+        rest = &r[end..];
+      } else if let Some(r) = rest.strip_prefix('[') {
+        let end = r.find(']')?;
+        let inner = r[..end].trim();
+        member = match inner.chars().next() {
+          Some(c) if c.is_ascii_digit() => inner.to_string(),
+          _ => self.lookup(inner)?.to_string(),
+        };
+        rest = &r[end + 1..];
+      } else {
+        return None;
+      }
+      value = match value {
+        Value::Map(entries) => entries.get(&member)?,
+        Value::Vector(items) => items.get(member.parse::<usize>().ok()?)?,
+        _ => return None,
       };
-    } else {
-      self.stack.insert(key, value);
     }
+    Some(value)
+  }
+  fn writable_key(&self, key: &String) -> Result<String, String> {
+    match self.get_real_key(key) {
+      Some((_, real_key)) if real_key.starts_with(ENV_NAMESPACE) => Err(format!(
+        "the variable '{}' is read-only (namespace '{}' of the process environment)",
+        real_key, ENV_NAMESPACE
+      )),
+      Some((_, real_key)) => match self.constant(&real_key) {
+        Some((name, origin)) => Err(format!(
+          "the variable '{}' is a constant and can't be modified (constant '{}' defined {})",
+          real_key, name, origin
+        )),
+        None => Ok(real_key),
+      },
+      None => Err(format!("invalid indirection key ('{}' not found)", key)),
+    }
+  }
+  fn constant(&self, key: &str) -> Option<(&String, &String)> {
+    self
+      .constants
+      .iter()
+      .find(|(name, _)| match key.strip_prefix(name.as_str()) {
+        Some(rest) => rest.is_empty() || rest.starts_with('.') || rest.starts_with('['),
+        None => false,
+      })
+  }
+  pub fn set_constant<V: Into<Value>>(
+    &mut self,
+    key: String,
+    value: V,
+    origin: String,
+  ) -> Option<String> {
+    let key = match self.writable_key(&key) {
+      Ok(k) => k,
+      Err(err) => return Some(err),
+    };
+    self.scopes[0].insert(key.to_string(), value.into());
+    self.constants.insert(key, origin);
+    None
+  }
+  pub fn set<V: Into<Value>>(&mut self, key: String, value: V) -> Option<String> {
+    let key = match self.writable_key(&key) {
+      Ok(k) => k,
+      Err(err) => return Some(err),
+    };
+    if let Some(scope) = self.scopes.last_mut() {
+      scope.insert(key, value.into());
+    }
+    None
+  }
+  pub fn set_global<V: Into<Value>>(&mut self, key: String, value: V) -> Option<String> {
+    let key = match self.writable_key(&key) {
+      Ok(k) => k,
+      Err(err) => return Some(err),
+    };
+    self.scopes[0].insert(key, value.into());
     None
   }
-  pub fn get(&self, key: &String) -> Result<Option<&String>, String> {
+  pub fn get_value(&self, key: &String) -> Result<Option<&Value>, String> {
     if key.starts_with("$") {
-      match self.stack.get(key) {
-        Some(real_key) => Ok(self.stack.get(real_key)),
+      match self.lookup(key) {
+        Some(real_key) => Ok(self.lookup(&real_key.to_string())),
         None => return Err(format!("invalid indirection key ('{}' not found)", key)),
       }
     } else {
-      Ok(self.stack.get(key))
+      Ok(self.lookup(key))
     }
   }
+  pub fn get(&self, key: &String) -> Result<Option<String>, String> {
+    self
+      .get_value(key)
+      .map(|value| value.map(|v| v.to_string()))
+  }
+  pub fn is_defined(&self, key: &String) -> bool {
+    matches!(self.get_value(key), Ok(Some(_)))
+  }
+  pub fn unset(&mut self, key: &String) -> Result<bool, String> {
+    let key = self.writable_key(key)?;
+    Ok(
+      self
+        .scopes
+        .iter_mut()
+        .rev()
+        .find_map(|scope| scope.remove(&key))
+        .is_some(),
+    )
+  }
   pub fn get_real_key(&self, key: &String) -> Option<(bool, String)> {
     if key.starts_with("$") {
-      match self.stack.get(key) {
+      match self.lookup(key) {
         Some(real_key) => Some((true, real_key.to_string())),
         None => None,
       }
@@ -55,12 +225,82 @@ impl Environment {
       Some((false, key.to_string()))
     }
   }
+  pub fn display_process(&self) -> String {
+    let mut variables = self.process.0.iter().collect::<Vec<_>>();
+    variables.sort_by(|a, b| a.0.cmp(b.0));
+    variables
+      .iter()
+      .map(|(key, value)| format!("{}{} -> {}", ENV_NAMESPACE, key, value))
+      .collect::<Vec<_>>()
+      .join("\n")
+  }
+  pub fn variables(&self) -> BTreeMap<String, Value> {
+    let mut variables: BTreeMap<String, Value> = BTreeMap::new();
+    for scope in self.scopes.iter().rev() {
+      for (key, value) in scope {
+        variables
+          .entry(key.to_string())
+          .or_insert_with(|| value.clone());
+      }
+    }
+    variables
+  }
+  // the blocks are only text once the document of their pass is transformed
+  pub fn block_sources(&self) -> BTreeMap<String, String> {
+    self
+      .blocks
+      .iter()
+      .map(|(key, parts)| {
+        let content = parts
+          .iter()
+          .filter_map(|part| match part {
+            Part::GeneratedText(s) => Some(&s[..]),
+            _ => None,
+          })
+          .collect::<String>();
+        (key.to_string(), content)
+      })
+      .collect()
+  }
+  pub fn push_scope(&mut self) {
+    self.scopes.push(HashMap::new());
+  }
+  pub fn pop_scope(&mut self) {
+    if self.scopes.len() > 1 {
+      self.scopes.pop();
+    }
+  }
+  pub fn scope_variables(&self) -> Vec<(&String, &Value)> {
+    if self.scopes.len() < 2 {
+      return vec![];
+    }
+    let mut variables = match self.scopes.last() {
+      Some(scope) => scope.iter().collect::<Vec<_>>(),
+      None => vec![],
+    };
+    variables.sort_by(|a, b| a.0.cmp(b.0));
+    variables
+  }
   pub fn set_block(&mut self, key: String, value: Vec<Part>) {
     self.blocks.insert(key, value);
   }
   pub fn get_block(&self, key: &String) -> Option<&Vec<Part>> {
     self.blocks.get(key)
   }
+  pub fn has_block(&self, key: &String) -> bool {
+    self.blocks.contains_key(key)
+  }
+  pub fn unset_block(&mut self, key: &String) -> bool {
+    self.blocks.remove(key).is_some()
+  }
+  pub fn add_dependency(&mut self, path: String) {
+    if !self.dependencies.contains(&path) {
+      self.dependencies.push(path);
+    }
+  }
+  pub fn dependencies(&self) -> &Vec<String> {
+    &self.dependencies
+  }
   pub fn transform(&mut self, doc: &Document) {
     for block in self.blocks.values_mut() {
       let mut destination: String = "".to_string();
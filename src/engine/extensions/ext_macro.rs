@@ -9,7 +9,8 @@ fn transform_to_text(v: &Value) -> String {
   match v {
     Value::Text(t) => t.to_string(),
     Value::Symbol(s) => s.to_string(),
-    Value::Number(n) => n.to_string(),
+    Value::Integer(n) => n.to_string(),
+    Value::Number(n) => super::format_number(*n),
     Value::Vector(v) => {
       if v.len() == 0 {
         "".to_string()
@@ -20,6 +21,7 @@ fn transform_to_text(v: &Value) -> String {
           .unwrap()
       }
     }
+    Value::Map(_) => v.to_string(),
     Value::True => "true".to_string(),
     Value::False | Value::Void => "".to_string(),
   }
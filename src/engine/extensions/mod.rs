@@ -7,21 +7,73 @@ pub mod ext_macro;
 #[cfg(feature = "engine-extensions")]
 pub mod ext_text;
 
+use std::collections::BTreeMap;
+use std::fmt;
+
 use crate::engine::Document;
 use crate::engine::Environment;
+use crate::utils::json;
 
 #[allow(dead_code)]
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Value {
   Text(String),
   Symbol(String),
+  Integer(i64),
   Number(f64),
   Vector(Vec<Value>),
+  Map(BTreeMap<String, Value>),
   True,
   False,
   Void,
 }
 
+impl fmt::Display for Value {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Value::Text(s) | Value::Symbol(s) => write!(f, "{}", s),
+      Value::Integer(n) => write!(f, "{}", n),
+      Value::Number(n) => write!(f, "{}", format_number(*n)),
+      Value::Vector(items) => write!(
+        f,
+        "{}",
+        items
+          .iter()
+          .map(|i| i.to_string())
+          .collect::<Vec<_>>()
+          .join("\n")
+      ),
+      Value::Map(_) => write!(f, "{}", json::to_json(self)),
+      Value::True => write!(f, "true"),
+      Value::False => write!(f, "false"),
+      Value::Void => Ok(()),
+    }
+  }
+}
+
+// 15 significant digits at most : '0.1 + 0.2' gives '0.3'
+pub fn format_number(n: f64) -> String {
+  if !n.is_finite() {
+    return n.to_string();
+  }
+  match format!("{:.14e}", n).parse::<f64>() {
+    Ok(rounded) => rounded.to_string(),
+    Err(_) => n.to_string(),
+  }
+}
+
+impl From<String> for Value {
+  fn from(s: String) -> Self {
+    Value::Text(s)
+  }
+}
+
+impl From<&str> for Value {
+  fn from(s: &str) -> Self {
+    Value::Text(s.to_string())
+  }
+}
+
 #[derive(Debug)]
 pub struct Context<'a> {
   pub begining: bool,
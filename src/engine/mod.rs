@@ -1,8 +1,12 @@
+pub mod batch;
 mod document;
 mod environment;
 pub mod extensions;
 mod parser;
+mod renderer;
 mod resolver;
+pub mod watch;
 
 pub use crate::engine::document::Document;
 pub use crate::engine::environment::Environment;
+pub use crate::engine::renderer::Renderer;
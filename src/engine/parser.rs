@@ -7,7 +7,7 @@ use std::fmt;
 // #[derive(PartialEq)]
 pub enum Token {
   Symbol(usize, usize),
-  Text(usize, usize),
+  Text(usize, usize, String),
   Space(TokenSpace),
   ParenthesisOpening,
   ParenthesisEnding,
@@ -20,13 +20,15 @@ pub enum Token {
   Pipe,
   Ampersand,
   Exclamation,
+  Question,
+  Tilde,
 }
 
 impl PartialEq<Token> for Token {
   fn eq(&self, other: &Token) -> bool {
     match (self, other) {
       (Token::Symbol(_, _), Token::Symbol(_, _)) => true,
-      (Token::Text(_, _), Token::Text(_, _)) => true,
+      (Token::Text(_, _, _), Token::Text(_, _, _)) => true,
       (Token::Space(_), Token::Space(_)) => true,
       (Token::ParenthesisOpening, Token::ParenthesisOpening) => true,
       (Token::ParenthesisEnding, Token::ParenthesisEnding) => true,
@@ -39,6 +41,8 @@ impl PartialEq<Token> for Token {
       (Token::Pipe, Token::Pipe) => true,
       (Token::Ampersand, Token::Ampersand) => true,
       (Token::Exclamation, Token::Exclamation) => true,
+      (Token::Question, Token::Question) => true,
+      (Token::Tilde, Token::Tilde) => true,
       _ => false,
     }
   }
@@ -48,7 +52,7 @@ impl fmt::Debug for Token {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     match self {
       Token::Symbol(s, e) => write!(f, "Token::Symbol({},{})", s, e),
-      Token::Text(s, e) => write!(f, "Token::Text({},{})", s, e),
+      Token::Text(s, e, _) => write!(f, "Token::Text({},{})", s, e),
       Token::Space(s) => write!(f, "Token::Space({:?})", s),
       Token::ParenthesisOpening => write!(f, "Token::ParenthesisOpening"),
       Token::ParenthesisEnding => write!(f, "Token::ParenthesisEnding"),
@@ -61,6 +65,8 @@ impl fmt::Debug for Token {
       Token::Pipe => write!(f, "Token::Pipe"),
       Token::Ampersand => write!(f, "Token::Ampersand"),
       Token::Exclamation => write!(f, "Token::Exclamation"),
+      Token::Question => write!(f, "Token::Question"),
+      Token::Tilde => write!(f, "Token::Tilde"),
     }
   }
 }
@@ -69,7 +75,7 @@ impl fmt::Display for Token {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     match self {
       Token::Symbol(s, e) => write!(f, "Token::Symbol({},{})", s, e),
-      Token::Text(s, e) => write!(f, "Token::Text({},{})", s, e),
+      Token::Text(s, e, _) => write!(f, "Token::Text({},{})", s, e),
       Token::Space(s) => write!(f, "Token::Space({:?})", s),
       Token::ParenthesisOpening => write!(f, "Token::ParenthesisOpening"),
       Token::ParenthesisEnding => write!(f, "Token::ParenthesisEnding"),
@@ -82,6 +88,8 @@ impl fmt::Display for Token {
       Token::Pipe => write!(f, "Token::Pipe"),
       Token::Ampersand => write!(f, "Token::Ampersand"),
       Token::Exclamation => write!(f, "Token::Exclamation"),
+      Token::Question => write!(f, "Token::Question"),
+      Token::Tilde => write!(f, "Token::Tilde"),
     }
   }
 }
@@ -97,11 +105,56 @@ pub enum TokenSpace {
 pub fn parse<'a>(source: &'a str) -> Result<Vec<Token>, InternalError> {
   let mut stack: Vec<Token> = vec![];
   let mut is_text: bool = false;
+  let mut text: String = String::new();
   let mut portion_start: usize = 0;
   let mut is_escaping: bool = false;
+  let mut unicode: Option<usize> = None;
   for (i, c) in source.char_indices() {
+    if let Some(start) = unicode {
+      match c {
+        '{' if i == start + 2 => (),
+        '}' if i > start + 3 => {
+          match u32::from_str_radix(&source[start + 3..i], 16)
+            .ok()
+            .and_then(char::from_u32)
+          {
+            Some(decoded) => text.push(decoded),
+            None => {
+              return Err(create_internal_error!(format!(
+                "bad unicode escape at {} (not a valid code point : '{}')",
+                start,
+                &source[start..=i]
+              )))
+            }
+          }
+          unicode = None;
+        }
+        c if i > start + 2 && c.is_ascii_hexdigit() => (),
+        _ => {
+          return Err(create_internal_error!(format!(
+            "bad unicode escape at {} (must be '\\u{{' + hexadecimal digits + '}}')",
+            start
+          )))
+        }
+      }
+      continue;
+    }
     if is_text == true && is_escaping == true {
       is_escaping = false;
+      match c {
+        'n' => text.push('\n'),
+        't' => text.push('\t'),
+        'r' => text.push('\r'),
+        'u' => unicode = Some(i - 1),
+        '\\' | '"' => text.push(c),
+        c => {
+          return Err(create_internal_error!(format!(
+            "unknown escape sequence '\\{}' at {}",
+            c,
+            i - 1
+          )))
+        }
+      }
       continue;
     } else if is_text == false && is_escaping == true {
       return Err(create_internal_error!(format!("bad escape at {}", i - 1)));
@@ -197,6 +250,20 @@ pub fn parse<'a>(source: &'a str) -> Result<Vec<Token>, InternalError> {
         stack.push(Token::Exclamation);
         portion_start = i + 1;
       }
+      '?' if is_text == false => {
+        if portion_start < i {
+          stack.push(Token::Symbol(portion_start, i));
+        }
+        stack.push(Token::Question);
+        portion_start = i + 1;
+      }
+      '~' if !is_text => {
+        if portion_start < i {
+          stack.push(Token::Symbol(portion_start, i));
+        }
+        stack.push(Token::Tilde);
+        portion_start = i + 1;
+      }
       '\\' => {
         if is_escaping {
           is_escaping = false;
@@ -208,13 +275,14 @@ pub fn parse<'a>(source: &'a str) -> Result<Vec<Token>, InternalError> {
       '"' => {
         if is_text {
           is_text = false;
-          stack.push(Token::Text(portion_start, i));
+          stack.push(Token::Text(portion_start, i, std::mem::take(&mut text)));
           portion_start = i + 1;
         } else {
           is_text = true;
           portion_start = i + 1;
         }
       }
+      c if is_text => text.push(c),
       _ => (),
     }
   }
@@ -0,0 +1,222 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::fs;
+use std::hash::Hash;
+use std::hash::Hasher;
+use std::path::Path;
+
+use crate::add_step_internal_error;
+use crate::create_internal_error;
+use crate::display_debug;
+use crate::display_debug_block;
+use crate::display_debug_title;
+use crate::engine::document::Part;
+use crate::engine::extensions::Value;
+use crate::engine::Document;
+use crate::engine::Environment;
+use crate::utils::conf::Configuration;
+use crate::utils::conf::Delimiters;
+use crate::utils::error::InternalError;
+use crate::utils::json;
+
+#[derive(Debug)]
+pub struct Renderer<'c> {
+  pub conf: &'c Configuration,
+  pub env: Environment,
+  delimiters: Delimiters,
+}
+
+impl<'c> Renderer<'c> {
+  pub fn new(conf: &'c Configuration, env: Environment) -> Self {
+    Renderer {
+      conf,
+      env,
+      delimiters: conf.delimiters.clone(),
+    }
+  }
+  pub fn from_conf(conf: &'c Configuration) -> Self {
+    Renderer::new(conf, Environment::from_args(conf))
+  }
+  pub fn render_path<P: AsRef<Path>>(&mut self, path: P) -> Result<String, InternalError> {
+    match fs::read_to_string(path.as_ref()) {
+      Ok(source) => self.render(&source, Some(path.as_ref().display().to_string())),
+      Err(err) => Err(create_internal_error!(
+        "A fatal error occurred during reading input from file",
+        format!("Path = {:?}", path.as_ref()),
+        format!("Error details = {}", err)
+      )),
+    }
+  }
+  pub fn render_str(&mut self, source: &str) -> Result<String, InternalError> {
+    self.render(source, None)
+  }
+  fn render(&mut self, source: &str, path: Option<String>) -> Result<String, InternalError> {
+    let conf = self.conf;
+    let mut doc = Document::from_str(conf, source);
+    doc.path = path;
+    if let Err(mut err) = doc.apply_pragma() {
+      return Err(add_step_internal_error!(
+        err,
+        "A fatal error occurred during reading the document pragma"
+      ));
+    }
+    // the blocks of the environment are written with these delimiters
+    self.delimiters = doc.delimiters.clone();
+    render_passes(&mut doc, &mut self.env, conf.is_reentrant)?;
+    if conf.skip_first_line {
+      doc.source = doc
+        .source
+        .split('\n')
+        .skip(1)
+        .collect::<Vec<_>>()
+        .join("\n");
+    }
+    Ok(doc.source)
+  }
+  // the variables and the blocks, rendered as a call in the final environment,
+  // sorted by name
+  pub fn dump_env(&self) -> Result<String, InternalError> {
+    let mut blocks: BTreeMap<String, Value> = BTreeMap::new();
+    for (key, source) in self.env.block_sources() {
+      let mut env = self.env.clone();
+      let mut doc = Document::new(self.conf, source);
+      doc.delimiters = self.delimiters.clone();
+      if let Err(mut err) = render_passes(&mut doc, &mut env, self.conf.is_reentrant) {
+        return Err(add_step_internal_error!(
+          err,
+          format!("Error during rendering the block '{}'", key)
+        ));
+      }
+      blocks.insert(key, Value::Text(doc.source));
+    }
+    let mut root: BTreeMap<String, Value> = BTreeMap::new();
+    root.insert("variables".to_string(), Value::Map(self.env.variables()));
+    root.insert("blocks".to_string(), Value::Map(blocks));
+    Ok(json::to_json_pretty(&Value::Map(root), 0))
+  }
+}
+
+pub fn render_passes(
+  doc: &mut Document,
+  env: &mut Environment,
+  reentrant: bool,
+) -> Result<(), InternalError> {
+  let conf = doc.conf;
+  let mut reentrance: usize = 0;
+  let mut history: Vec<u64> = vec![fingerprint(&doc.source, env)];
+  let mut cycle: Option<usize> = None;
+  doc.reentrant = reentrant;
+  loop {
+    display_debug_title!(conf, "Reentrant step n°{}", reentrance);
+    doc.reentrance = reentrance;
+    match doc.parse_parts() {
+      Ok(r) => {
+        if r {
+          let (n_parts, n_statictexts) = doc.stack_len();
+          display_debug_block!(
+            conf,
+            "Parse parts",
+            "parts found (total = {}, static text = {})",
+            n_parts,
+            n_statictexts
+          );
+        } else {
+          display_debug_block!(conf, "Parse parts", "Nothing to do");
+          break;
+        }
+      }
+      Err(mut err) => {
+        return Err(add_step_internal_error!(
+          err,
+          "A fatal error occurred during parsing document"
+        ))
+      }
+    }
+    if let Some(previous) = cycle {
+      if let Some(mut err) = still_changing(doc) {
+        return Err(add_step_internal_error!(
+          err,
+          format!(
+            "Cycle detected : the document produced by the pass n°{} is the one read by the pass n°{}",
+            reentrance - 1,
+            previous
+          ),
+          "the document would be rendered indefinitely"
+        ));
+      }
+    }
+    // only the passes changing the document are counted (the parts are
+    // located before their resolution)
+    let pending = (reentrance >= conf.max_passes).then(|| still_changing(doc));
+    match doc.resolve(env) {
+      Ok(changed) => {
+        if changed {
+          display_debug_block!(conf, "Resolve parts", "Document is changed");
+          if let Some(pending) = pending {
+            let mut err = pending.unwrap_or_else(|| {
+              create_internal_error!("Scopes ('with' or 'autoescape' statements) still to resolve")
+            });
+            return Err(add_step_internal_error!(
+              err,
+              format!(
+                "The maximum number of passes is reached ({}) and the document is still changing",
+                conf.max_passes
+              ),
+              "the limit can be changed with the --max-passes argument"
+            ));
+          }
+          doc.transform(env);
+        } else {
+          if reentrance > 0 {
+            display_debug_block!(conf, "Resolve parts", "Document is not changed");
+          }
+          break;
+        }
+      }
+      Err(mut err) => return Err(add_step_internal_error!(err, "Error during resolving")),
+    }
+    if !reentrant {
+      break;
+    }
+    reentrance += 1;
+    let current = fingerprint(&doc.source, env);
+    cycle = history.iter().position(|h| *h == current);
+    history.push(current);
+  }
+  Ok(())
+}
+
+// the environment too : a block can change without changing the document
+fn fingerprint(source: &str, env: &Environment) -> u64 {
+  let mut hasher = DefaultHasher::new();
+  source.hash(&mut hasher);
+  json::to_json(&Value::Map(env.variables())).hash(&mut hasher);
+  env.block_sources().hash(&mut hasher);
+  hasher.finish()
+}
+
+fn still_changing(doc: &Document) -> Option<InternalError> {
+  // the scopes ('with' statements) and the escapings ('autoescape'
+  // statements) only wrap what is still changing
+  let changing = |position: usize| {
+    !matches!(
+      doc.keyword(position),
+      Some("with") | Some("endwith") | Some("autoescape") | Some("endautoescape")
+    )
+  };
+  let (s, e) = doc
+    .stack
+    .iter()
+    .enumerate()
+    .filter(|(position, _)| changing(*position))
+    .find_map(|(_, part)| match *part {
+      Part::Statement(s, e) | Part::Expression(s, e) => Some((s, e)),
+      _ => None,
+    })?;
+  let mut err = create_internal_error!(
+    "Statement or expression still producing changes",
+    format!("found = '{}'", doc.source[s..e].trim())
+  );
+  err.set_location(doc.locate(s, e));
+  Some(err)
+}
@@ -1,140 +1,386 @@
 use crate::create_internal_error;
 use crate::engine::document::Part;
+use crate::engine::extensions::Value;
 use crate::engine::parser;
-use crate::engine::resolver::add_string_to_another;
+use crate::engine::parser::Token;
+use crate::engine::resolver::statement::unit_execute::resolve_filters;
+use crate::engine::resolver::statement::unit_if::evaluate_condition;
 use crate::engine::Document;
 use crate::engine::Environment;
 use crate::utils::error::InternalError;
 
-pub fn resolve_expression<'a>(
-  _doc: &'a Document,
-  expr: &'a str,
-  env: &mut Environment,
-) -> Result<Part, InternalError> {
-  let source: &str = &expr[2..expr.len() - 2];
-  let tokens: Vec<parser::Token> = match parser::parse(source) {
-    Ok(t) => t,
-    Err(err) => return Err(err),
-  };
-  let mut output = "".to_string();
-  let mut iter = tokens.iter().peekable();
-  let mut is_begining: bool = true;
-  loop {
-    let token = match iter.next() {
-      Some(t) => t,
-      None => break,
+fn is_fallback_operator(source: &str, token: &Token) -> bool {
+  match token {
+    Token::Question => true,
+    &Token::Symbol(s, e) => &source[s..e] == "or",
+    _ => false,
+  }
+}
+
+fn number_literal(symbol: &str) -> Option<Value> {
+  match symbol.chars().next() {
+    Some(c) if c.is_ascii_digit() => parse_number(symbol),
+    _ => None,
+  }
+}
+
+fn parse_number(text: &str) -> Option<Value> {
+  match (text.parse::<i64>(), text.parse::<f64>()) {
+    (Ok(n), _) => Some(Value::Integer(n)),
+    (_, Ok(n)) => Some(Value::Number(n)),
+    _ => None,
+  }
+}
+
+fn as_number(value: &Value) -> Option<Value> {
+  match value {
+    Value::Integer(_) | Value::Number(_) => Some(value.clone()),
+    // a written number only ('inf' or 'nan' stay texts)
+    Value::Text(s) => match s.trim().trim_start_matches(['-', '+']).chars().next() {
+      Some(c) if c.is_ascii_digit() => parse_number(s.trim()),
+      _ => None,
+    },
+    _ => None,
+  }
+}
+
+fn as_float(value: &Value) -> f64 {
+  match value {
+    Value::Integer(n) => *n as f64,
+    Value::Number(n) => *n,
+    _ => f64::NAN,
+  }
+}
+
+fn operate(a: &Value, b: &Value, operator: &Token) -> Result<Value, InternalError> {
+  if let (Value::Integer(x), Value::Integer(y)) = (a, b) {
+    let result = match operator {
+      Token::Plus => x.checked_add(*y),
+      Token::Minus => x.checked_sub(*y),
+      Token::Multiply => x.checked_mul(*y),
+      _ => match x.checked_rem(*y) {
+        Some(0) => x.checked_div(*y),
+        // a non-exact division gives a decimal number
+        Some(_) => return Ok(Value::Number(*x as f64 / *y as f64)),
+        None => None,
+      },
     };
-    match token {
-      &parser::Token::Text(s, e) if is_begining == false => {
-        return Err(
-          create_internal_error!(
-            format!(
-              "Invalid position's text in expression: the operator '+' is likely missing (found '{}' at {} ~> {})",
-              &source[s..e],
-              s,
-              e
-            )
-          )
-        )
+    return match result {
+      Some(n) => Ok(Value::Integer(n)),
+      None => Err(create_internal_error!(format!(
+        "Integer overflow : the result of '{} {} {}' doesn't fit on 64 bits",
+        x,
+        match operator {
+          Token::Plus => "+",
+          Token::Minus => "-",
+          Token::Multiply => "*",
+          _ => "/",
+        },
+        y
+      ))),
+    };
+  }
+  let (x, y) = (as_float(a), as_float(b));
+  Ok(Value::Number(match operator {
+    Token::Plus => x + y,
+    Token::Minus => x - y,
+    Token::Multiply => x * y,
+    _ => x / y,
+  }))
+}
+
+// expression := term (('+' | '-') term)*
+// term       := factor (('*' | '/') factor)*
+// factor     := '-' factor | '(' expression ')' | operand (('?' | 'or') operand)*
+struct Evaluator<'a, 'e> {
+  source: &'a str,
+  tokens: &'e [&'a Token],
+  position: usize,
+  env: &'e Environment,
+}
+
+impl<'a, 'e> Evaluator<'a, 'e> {
+  fn peek(&self) -> Option<&'a Token> {
+    self.tokens.get(self.position).copied()
+  }
+  fn expression(&mut self) -> Result<Value, InternalError> {
+    let mut value = self.term()?;
+    loop {
+      match self.peek() {
+        Some(Token::Plus) => {
+          self.position += 1;
+          let other = self.term()?;
+          value = match (as_number(&value), as_number(&other)) {
+            (Some(a), Some(b)) => operate(&a, &b, &Token::Plus)?,
+            _ => Value::Text(format!("{}{}", value, other)),
+          };
+        }
+        Some(Token::Tilde) => {
+          self.position += 1;
+          let other = self.term()?;
+          value = Value::Text(format!("{}{}", value, other));
+        }
+        Some(Token::Minus) => {
+          self.position += 1;
+          let other = self.term()?;
+          value = operate(
+            &self.number(&value, "-")?,
+            &self.number(&other, "-")?,
+            &Token::Minus,
+          )?;
+        }
+        _ => return Ok(value),
       }
-      &parser::Token::Symbol(s, e) if is_begining == false => {
-        return Err(
-          create_internal_error!(
-            format!(
-              "Invalid position's symbol in expression: the operator '+' is likely missing (found '{}' at {} ~> {})",
-              &source[s..e],
-              s,
-              e
-            )
-          )
-        )
+    }
+  }
+  fn term(&mut self) -> Result<Value, InternalError> {
+    let mut value = self.factor()?;
+    loop {
+      match self.peek() {
+        Some(Token::Multiply) => {
+          self.position += 1;
+          let other = self.factor()?;
+          value = operate(
+            &self.number(&value, "*")?,
+            &self.number(&other, "*")?,
+            &Token::Multiply,
+          )?;
+        }
+        Some(Token::Divide) => {
+          self.position += 1;
+          let other = self.factor()?;
+          let divisor = self.number(&other, "/")?;
+          if as_float(&divisor) == 0.0 {
+            return Err(create_internal_error!(format!(
+              "Division by zero (the divisor '{}' is null)",
+              other
+            )));
+          }
+          value = operate(&self.number(&value, "/")?, &divisor, &Token::Divide)?;
+        }
+        _ => return Ok(value),
       }
-      parser::Token::Plus if is_begining => {
-        return Err(
-          create_internal_error!(
-            "The operator '+' is not allowed at the beginning of the expression or more than once in a row"
-          )
+    }
+  }
+  fn factor(&mut self) -> Result<Value, InternalError> {
+    match self.peek() {
+      Some(Token::Minus) => {
+        self.position += 1;
+        let value = self.factor()?;
+        operate(
+          &Value::Integer(0),
+          &self.number(&value, "-")?,
+          &Token::Minus,
         )
       }
-      parser::Token::Text(s, e) if is_begining => {
-        add_string_to_another(&mut source[*s..*e].to_string(), &mut output);
-        is_begining = false;
-      }
-      parser::Token::Symbol(s, e) if is_begining => {
-        let symbol = source[*s..*e].to_string();
-        match env.get(&symbol) {
-          Ok(Some(v)) => output.push_str(v),
-          Ok(None) => match env.get_real_key(&symbol) {
-            Some((true, real_key)) => return Err(create_internal_error!(format!(
-              "Undefined indirection variable '{}' in environment (original : '{}')",
-              real_key,
-              symbol
-            ))),
-            Some((false, real_key)) => return Err(create_internal_error!(format!(
-              "Undefined variable '{}' in environment (no indirection)",
-              real_key
-            ))),
-            None => return Err(create_internal_error!(format!(
-              "Undefined indirection variable '{}' in environment (original value)",
-              symbol
-            )))
+      Some(Token::ParenthesisOpening) => {
+        self.position += 1;
+        let value = self.expression()?;
+        match self.peek() {
+          Some(Token::ParenthesisEnding) => {
+            self.position += 1;
+            Ok(value)
           }
-          Err(err) => return Err(create_internal_error!(
-            "Error during getting variable",
-            err
+          _ => Err(create_internal_error!(
+            "A parenthesis is opened and not closed"
           )),
         }
-        is_begining = false;
       }
-      parser::Token::Plus if is_begining == false => {
-        loop {
-          match iter.next() {
-            Some(parser::Token::Text(s, e)) => {
-              add_string_to_another(&mut source[*s..*e].to_string(), &mut output);
-              is_begining = false;
-              break;
-            }
-            Some(parser::Token::Symbol(s, e)) => {
-              let symbol = &source[*s..*e].to_string();
-              match env.get(&symbol) {
-                Ok(Some(v)) => add_string_to_another(
-                  &mut v[..].to_string(), // pas terrible...
-                  &mut output,
-                ),
-                Ok(None) => {
-                  return Err(create_internal_error!(format!(
-                    "Undefined variable '{}' in environment",
+      _ => self.operand(),
+    }
+  }
+  fn number(&self, value: &Value, operator: &str) -> Result<Value, InternalError> {
+    match as_number(value) {
+      Some(n) => Ok(n),
+      None => Err(create_internal_error!(format!(
+        "The operand '{}' of the operator '{}' is not a number",
+        value, operator
+      ))),
+    }
+  }
+  fn operand(&mut self) -> Result<Value, InternalError> {
+    let source = self.source;
+    let mut alternatives: Vec<&Token> = vec![];
+    loop {
+      match self.peek() {
+        Some(t @ Token::Text(_, _, _)) | Some(t @ Token::Symbol(_, _)) if !is_fallback_operator(source, t) => {
+          alternatives.push(t)
+        }
+        Some(Token::Plus) | Some(Token::Tilde) if self.position == 0 => {
+          return Err(create_internal_error!(
+            "The operators '+' and '~' are not allowed at the beginning of the expression or more than once in a row"
+          ))
+        }
+        Some(t) => {
+          return Err(create_internal_error!(format!(
+            "Token {} not authorized as operand (must be text, symbol, number or parenthesis)",
+            t
+          )))
+        }
+        None => {
+          return Err(create_internal_error!(
+            "Invalid ending : an operator without symbol or text after"
+          ))
+        }
+      }
+      self.position += 1;
+      match self.peek() {
+        Some(t) if is_fallback_operator(source, t) => self.position += 1,
+        _ => break,
+      }
+    }
+    let last = alternatives.len() - 1;
+    for (i, alternative) in alternatives.iter().enumerate() {
+      match **alternative {
+        Token::Text(_, _, ref text) => return Ok(Value::Text(text.clone())),
+        Token::Symbol(s, e) => {
+          let symbol = source[s..e].to_string();
+          if let Some(n) = number_literal(&symbol) {
+            return Ok(n);
+          }
+          match self.env.get_value(&symbol) {
+            Ok(Some(v)) => return Ok(v.clone()),
+            Ok(None) if i < last => continue,
+            Err(_) if i < last => continue,
+            Ok(None) => match self.env.get_real_key(&symbol) {
+              Some((true, real_key)) => {
+                return Err(
+                  create_internal_error!(format!(
+                    "Undefined indirection variable '{}' in environment (original : '{}')",
+                    real_key, symbol
+                  ))
+                  .with_undefined(real_key),
+                )
+              }
+              Some((false, real_key)) => {
+                return Err(
+                  create_internal_error!(format!(
+                    "Undefined variable '{}' in environment (no indirection)",
+                    real_key
+                  ))
+                  .with_undefined(real_key),
+                )
+              }
+              None => {
+                return Err(
+                  create_internal_error!(format!(
+                    "Undefined indirection variable '{}' in environment (original value)",
                     symbol
-                  )))
-                },
-                Err(err) => return Err(create_internal_error!(err)),
+                  ))
+                  .with_undefined(symbol),
+                )
               }
-              is_begining = false;
-              break;
-            }
-            Some(parser::Token::Space(_)) => continue,
-            Some(t) => {
-              return Err(create_internal_error!(format!(
-                "Token {} not authorized in second part of expression (after '+'; must be text or symbol)",
-                t
-              )))
+            },
+            Err(err) => {
+              return Err(
+                create_internal_error!("Error during getting variable", err).with_undefined(symbol),
+              )
             }
-            None => {
-              return Err(create_internal_error!(
-                "No token found for second part of expression (after '+')"
-              ))
-            }
-          };
+          }
         }
+        _ => (),
       }
-      parser::Token::Space(_) => (),
-      t => {
-        return Err(create_internal_error!(format!(
-          "Token {} not authorized in second part of expression (at beginning; must be text or symbol)",
-          t
-        )))
+    }
+    Ok(Value::Void)
+  }
+}
+
+pub fn evaluate<'a>(
+  source: &'a str,
+  tokens: &[&'a Token],
+  env: &Environment,
+) -> Result<Value, InternalError> {
+  let mut evaluator = Evaluator {
+    source,
+    tokens,
+    position: 0,
+    env,
+  };
+  let value = evaluator.expression()?;
+  match evaluator.peek() {
+    None => Ok(value),
+    Some(&Token::Text(s, e, _)) | Some(&Token::Symbol(s, e)) => Err(create_internal_error!(format!(
+      "Invalid position's text or symbol in expression: the operator '+' is likely missing (found '{}' at {} ~> {})",
+      &source[s..e],
+      s,
+      e
+    ))),
+    Some(Token::ParenthesisEnding) => Err(create_internal_error!(
+      "A parenthesis is closed and not opened before"
+    )),
+    Some(t) => Err(create_internal_error!(format!(
+      "Token {} not authorized in expression (must be an operator or a fallback operator)",
+      t
+    ))),
+  }
+}
+
+pub fn resolve_expression<'a>(
+  doc: &'a Document,
+  doc_position: usize,
+  source: &'a str,
+  env: &mut Environment,
+) -> Result<Part, InternalError> {
+  let all_tokens: Vec<parser::Token> = match parser::parse(source) {
+    Ok(t) => t,
+    Err(err) => return Err(err),
+  };
+  let keyword = |keyword: &str, tokens: &[Token]| {
+    tokens
+      .iter()
+      .position(|t| matches!(*t, Token::Symbol(s, e) if &source[s..e] == keyword))
+  };
+  let mut tokens: &[Token] = &all_tokens[..];
+  if let Some(p) = keyword("if", tokens) {
+    let rest = &tokens[p + 1..];
+    let (condition, otherwise) = match keyword("else", rest) {
+      Some(e) => (&rest[..e], &rest[e + 1..]),
+      None => (rest, &rest[rest.len()..]),
+    };
+    tokens = match evaluate_condition(env, source, condition.iter().collect())? {
+      true => &tokens[..p],
+      false => otherwise,
+    };
+  }
+  let (tokens, filters) = match tokens.iter().position(|t| *t == Token::Pipe) {
+    Some(p) => (&tokens[..p], Some(&tokens[p + 1..])),
+    None => (tokens, None),
+  };
+  let (filters, safe) = match filters {
+    Some(filters) => {
+      let last = filters.iter().rposition(|t| *t == Token::Pipe);
+      let mut marker = filters[last.map_or(0, |p| p + 1)..]
+        .iter()
+        .filter(|t| !matches!(t, Token::Space(_)));
+      match (marker.next(), marker.next()) {
+        (Some(&Token::Symbol(s, e)), None) if &source[s..e] == "safe" => {
+          (last.map(|p| &filters[..p]), true)
+        }
+        _ => (Some(filters), false),
       }
     }
+    None => (None, false),
+  };
+  let tokens: Vec<&Token> = tokens
+    .iter()
+    .filter(|t| !matches!(t, Token::Space(_)))
+    .collect();
+  let mut output = match tokens.is_empty() {
+    true => "".to_string(),
+    false => evaluate(source, &tokens, env)?.to_string(),
+  };
+  if let Some(filters) = filters {
+    output = resolve_filters(
+      doc,
+      doc_position,
+      env,
+      source,
+      output,
+      &mut filters.iter().peekable(),
+    )?;
+  }
+  if !safe {
+    output = env.escaping().escape(&output);
   }
   Ok(Part::GeneratedText(output))
 }
@@ -2,75 +2,131 @@ pub mod expression;
 pub mod statement;
 
 use crate::add_step_internal_error;
+use crate::engine::document::Node;
 use crate::engine::document::Part;
 use crate::engine::resolver::expression::resolve_expression;
 use crate::engine::resolver::statement::resolve_statement;
 use crate::engine::resolver::statement::Resolved;
 use crate::engine::Document;
 use crate::engine::Environment;
+use crate::utils::conf::UndefinedPolicy;
 use crate::utils::error::InternalError;
 
-pub fn add_string_to_another(s1: &mut String, s2: &mut String) {
-  s1.retain(|c| !r#"\"#.contains(c));
-  s2.push_str(s1);
+const UNDEFINED_POLICY_KEYWORDS: [&str; 4] = ["if", "for", "include", "call"];
+
+fn is_undefined(doc: &Document, position: usize, env: &Environment, err: &InternalError) -> bool {
+  if err.undefined().is_none() || *env.undefined_policy() == UndefinedPolicy::Error {
+    return false;
+  }
+  match doc.stack_get(position) {
+    Some(Part::Expression(_, _)) => true,
+    Some(Part::Statement(_, _)) => {
+      matches!(doc.keyword(position), Some(k) if UNDEFINED_POLICY_KEYWORDS.contains(&k))
+    }
+    _ => false,
+  }
+}
+
+fn kept(doc: &Document, node: &Node) -> Option<Part> {
+  let (opening, ending) = match *node {
+    Node::Unit(position) => (position, position),
+    Node::Bordered(opening, _, ending) => (opening, ending),
+  };
+  match (doc.stack_get(opening)?, doc.stack_get(ending)?) {
+    (&Part::Statement(s, _), &Part::Statement(_, e))
+    | (&Part::Expression(s, _), &Part::Expression(_, e)) => Some(Part::StaticText(s, e)),
+    _ => None,
+  }
 }
 
 pub fn resolve<'a>(doc: &'a Document, env: &mut Environment) -> Result<Resolved, InternalError> {
-  let mut position: usize = 0;
-  let (max, _) = doc.stack_len();
-  let mut result: Vec<Part> = vec![];
   let mut changed: bool = false;
-  loop {
-    if position >= max {
-      break;
-    }
+  let mut stack: Vec<Part> = vec![];
+  let mut producers: Vec<(usize, usize)> = vec![];
+  for node in &doc.tree {
+    let parts = resolve_nodes(doc, std::slice::from_ref(node), env, &mut changed)?;
+    let producer = match node {
+      Node::Unit(p) | Node::Bordered(p, _, _) => match doc.stack_get(*p) {
+        Some(&Part::StaticText(s, e))
+        | Some(&Part::Statement(s, e))
+        | Some(&Part::Expression(s, e)) => (s, e),
+        _ => (0, 0),
+      },
+    };
+    producers.extend(parts.iter().map(|_| producer));
+    stack.extend(parts);
+  }
+  Ok(Resolved {
+    changed,
+    stack,
+    producers,
+  })
+}
+
+pub fn resolve_nodes(
+  doc: &Document,
+  nodes: &[Node],
+  env: &mut Environment,
+  changed: &mut bool,
+) -> Result<Vec<Part>, InternalError> {
+  let mut result: Vec<Part> = vec![];
+  for node in nodes {
+    let position = match *node {
+      Node::Unit(position) => position,
+      Node::Bordered(opening, _, _) => opening,
+    };
     match doc.stack_get(position) {
       Some(&Part::StaticText(s, e)) => result.push(Part::StaticText(s, e)),
       Some(&Part::Expression(s, e)) => {
-        match resolve_expression(doc, &doc.source[s..e], env) {
+        match resolve_expression(doc, position, doc.expression_source(s, e), env) {
           Ok(p) => result.push(p),
-          Err(mut err) => return Err(
-            add_step_internal_error!(
-              err,
-              "Error in expression",
-              format!("must be in the following form = '{{{{ text or symbol (+ text or symbol (+ ...)) }}}}'"),
-              format!("target expression (here with trim !) = '{}'", &doc.source[s+2..e-2].trim()),
-              format!("real position of expression in document = {} -> {}", s, e)
-            )
-          ),
+          Err(err) if is_undefined(doc, position, env, &err) => {
+            // kept as written : not a change (the document can be stable)
+            if *env.undefined_policy() == UndefinedPolicy::Keep {
+              result.extend(kept(doc, node));
+              continue;
+            }
+          }
+          Err(mut err) => {
+            err.set_location(doc.locate(s, e));
+            return Err(
+              add_step_internal_error!(
+                err,
+                "Error in expression",
+                format!("must be in the following form = '{{{{ text or symbol (? fallback) (+ text or symbol (+ ...)) (| filter (| ...)) }}}}'"),
+                format!("target expression (here with trim !) = '{}'", doc.expression_source(s, e).trim())
+              )
+            );
+          }
         }
-        changed = true;
+        *changed = true;
       }
       Some(&Part::Statement(s, e)) => {
-        match resolve_statement(doc, position, &doc.source[s..e], env) {
-          Ok((v, p)) => {
-            if v.len() > 0 {
-              result.extend(v.into_iter());
+        match resolve_statement(doc, position, node, doc.statement_source(s, e), env) {
+          Ok(v) => result.extend(v),
+          Err(err) if is_undefined(doc, position, env, &err) => {
+            if *env.undefined_policy() == UndefinedPolicy::Keep {
+              result.extend(kept(doc, node));
+              continue;
             }
-            position += p;
           }
           Err(mut err) => {
+            err.set_location(doc.locate(s, e));
             return Err(add_step_internal_error!(
               err,
               "Error in statement",
               format!("each statement has a different grammar"),
               format!(
                 "target expression (here with trim !) = '{}'",
-                &doc.source[s + 2..e - 2].trim()
-              ),
-              format!("real position of expression in document = {} -> {}", s, e)
-            ))
+                doc.statement_source(s, e).trim()
+              )
+            ));
           }
         }
-        changed = true;
+        *changed = true;
       }
-      Some(Part::GeneratedText(_)) | Some(Part::Comment(_, _)) => (),
-      None => break,
+      Some(Part::GeneratedText(_)) | Some(Part::Comment(_, _)) | None => (),
     }
-    position += 1;
   }
-  Ok(Resolved {
-    changed: changed,
-    stack: result,
-  })
+  Ok(result)
 }
@@ -1,5 +1,7 @@
+pub mod unit_autoescape;
 pub mod unit_block;
 pub mod unit_call;
+pub mod unit_capture;
 pub mod unit_execute;
 pub mod unit_find;
 pub mod unit_for;
@@ -7,18 +9,23 @@ pub mod unit_if;
 pub mod unit_include;
 pub mod unit_raw;
 pub mod unit_set;
+pub mod unit_unset;
+pub mod unit_with;
 
 use crate::add_step_internal_error;
 use crate::create_internal_error;
 use crate::engine::document::Document;
+use crate::engine::document::Node;
 use crate::engine::document::Part;
 use crate::engine::environment::Environment;
 use crate::engine::parser;
 use crate::engine::parser::Token;
 use crate::utils::error::InternalError;
 
+use crate::engine::resolver::statement::unit_autoescape::resolve_unit as resolve_statement_autoescape;
 use crate::engine::resolver::statement::unit_block::resolve_unit as resolve_statement_block;
 use crate::engine::resolver::statement::unit_call::resolve_unit as resolve_statement_call;
+use crate::engine::resolver::statement::unit_capture::resolve_unit as resolve_statement_capture;
 use crate::engine::resolver::statement::unit_execute::resolve_unit as resolve_statement_execute;
 use crate::engine::resolver::statement::unit_find::resolve_unit as resolve_statement_find;
 use crate::engine::resolver::statement::unit_for::resolve_unit as resolve_statement_for;
@@ -26,27 +33,69 @@ use crate::engine::resolver::statement::unit_if::resolve_unit as resolve_stateme
 use crate::engine::resolver::statement::unit_include::resolve_unit as resolve_statement_include;
 use crate::engine::resolver::statement::unit_raw::resolve_unit as resolve_statement_raw;
 use crate::engine::resolver::statement::unit_set::resolve_unit as resolve_statement_set;
+use crate::engine::resolver::statement::unit_unset::resolve_unit as resolve_statement_unset;
+use crate::engine::resolver::statement::unit_with::resolve_unit as resolve_statement_with;
 
 #[derive(Debug)]
 pub struct Resolved {
   pub changed: bool,
   pub stack: Vec<Part>,
+  // for each part of the stack, the span of the node producing it
+  pub producers: Vec<(usize, usize)>,
+}
+
+fn bordered(doc: &Document, keyword: &str, node: &Node) -> Result<Vec<Part>, InternalError> {
+  match node {
+    Node::Bordered(_, children, _) => Ok(doc.parts(children)),
+    Node::Unit(_) => Err(create_internal_error!(format!(
+      "the '{}' statement is bordered and must have an ending",
+      keyword
+    ))),
+  }
+}
+
+fn children<'a>(keyword: &str, node: &'a Node) -> Result<&'a [Node], InternalError> {
+  match node {
+    Node::Bordered(_, children, _) => Ok(children),
+    Node::Unit(_) => Err(create_internal_error!(format!(
+      "the '{}' statement is bordered and must have an ending",
+      keyword
+    ))),
+  }
+}
+
+fn is_pending(doc: &Document, body: &[Part]) -> bool {
+  body.iter().any(|part| match part {
+    Part::Statement(_, _) | Part::Expression(_, _) => true,
+    Part::GeneratedText(text) => doc.delimiters.is_opened_in(text),
+    _ => false,
+  })
+}
+
+// a scope is only needed by the parts resolved in a following pass
+fn scoped(doc: &Document, parts: Vec<Part>) -> Vec<Part> {
+  if !doc.reentrant || !is_pending(doc, &parts) {
+    return parts;
+  }
+  let mut result = vec![Part::GeneratedText(doc.delimiters.scope("with"))];
+  result.extend(parts);
+  result.push(Part::GeneratedText(doc.delimiters.scope("endwith")));
+  result
 }
 
 pub fn resolve_statement<'a>(
   doc: &'a Document,
   doc_position: usize,
-  expr: &'a str,
+  node: &'a Node,
+  source: &'a str,
   env: &mut Environment,
-) -> Result<(Vec<Part>, usize), InternalError> {
-  let source: &str = &expr[2..expr.len() - 2];
+) -> Result<Vec<Part>, InternalError> {
   let tokens: Vec<parser::Token> = match parser::parse(source) {
     Ok(t) => t,
     Err(err) => return Err(err),
   };
   let mut output: Vec<Part> = vec![];
   let mut iter = tokens.iter().peekable();
-  let mut position_skip: usize = 0;
   loop {
     let token = match iter.next() {
       Some(t) => t,
@@ -54,11 +103,8 @@ pub fn resolve_statement<'a>(
     };
     match token {
       &parser::Token::Symbol(s, e) => match &source[s..e] {
-        "block" => match resolve_statement_block(doc, doc_position, env, source, &mut iter) {
-          Ok(p) => {
-            position_skip = p;
-            break;
-          }
+        "block" => match resolve_statement_block(&bordered(doc, "block", node)?, env, source, &mut iter) {
+          Ok(_) => break,
           Err(mut err) => {
             return Err(add_step_internal_error!(
               err,
@@ -70,7 +116,7 @@ pub fn resolve_statement<'a>(
         "call" => match resolve_statement_call(env, source, &mut iter) {
           Ok(v) => {
             if v.len() > 0 {
-              output.extend(v);
+              output.extend(scoped(doc, v));
             }
             break;
           }
@@ -84,7 +130,7 @@ pub fn resolve_statement<'a>(
         },
         "include" => match resolve_statement_include(env, source, &mut iter) {
           Ok(v) => {
-            output.push(v);
+            output.extend(scoped(doc, vec![v]));
             break;
           }
           Err(mut err) => {
@@ -95,10 +141,9 @@ pub fn resolve_statement<'a>(
             ))
           }
         },
-        "if" => match resolve_statement_if(doc, doc_position, env, source, &mut iter) {
-          Ok((v, p)) => {
+        "if" => match resolve_statement_if(&bordered(doc, "if", node)?, env, source, &mut iter) {
+          Ok(v) => {
             output.extend(v);
-            position_skip = p;
             break;
           }
           Err(mut err) => {
@@ -109,10 +154,9 @@ pub fn resolve_statement<'a>(
             ))
           }
         },
-        "for" => match resolve_statement_for(doc, doc_position, env, source, &mut iter) {
-          Ok((v, p)) => {
+        "for" => match resolve_statement_for(doc, &bordered(doc, "for", node)?, env, source, &mut iter) {
+          Ok(v) => {
             output.extend(v);
-            position_skip = p;
             break;
           }
           Err(mut err) => {
@@ -123,10 +167,9 @@ pub fn resolve_statement<'a>(
             ))
           }
         },
-        "raw" => match resolve_statement_raw(doc, doc_position) {
-          Ok((v, p)) => {
+        "raw" => match resolve_statement_raw(&bordered(doc, "raw", node)?) {
+          Ok(v) => {
             output.extend(v);
-            position_skip = p;
             break;
           }
           Err(mut err) => {
@@ -137,7 +180,46 @@ pub fn resolve_statement<'a>(
             ))
           }
         },
-        "set" => match resolve_statement_set(env, source, &mut iter) {
+        "with" => match resolve_statement_with(doc, children("with", node)?, env, source, &mut iter) {
+          Ok(v) => {
+            output.extend(v);
+            break;
+          }
+          Err(mut err) => {
+            return Err(add_step_internal_error!(
+              err,
+              "error in 'with' statement",
+              format!("source = '{}'", source.trim()),
+              "must be = 'with ([symbol] = [text or symbol] ...)'"
+            ))
+          }
+        },
+        "autoescape" => match resolve_statement_autoescape(doc, children("autoescape", node)?, env, source, &mut iter) {
+          Ok(v) => {
+            output.extend(v);
+            break;
+          }
+          Err(mut err) => {
+            return Err(add_step_internal_error!(
+              err,
+              "error in 'autoescape' statement",
+              format!("source = '{}'", source.trim()),
+              "must be = 'autoescape ['html' or 'xml' or 'shell' or 'json' or 'none']'"
+            ))
+          }
+        },
+        "capture" => match resolve_statement_capture(doc, doc_position, children("capture", node)?, env, source, &mut iter) {
+          Ok(_) => break,
+          Err(mut err) => {
+            return Err(add_step_internal_error!(
+              err,
+              "error in 'capture' statement",
+              format!("source = '{}'", source.trim()),
+              "must be = 'capture [symbol]'"
+            ))
+          }
+        },
+        "set" => match resolve_statement_set(env, source, &mut iter, None) {
           Ok(_) => break,
           Err(mut err) => {
             return Err(add_step_internal_error!(
@@ -148,6 +230,34 @@ pub fn resolve_statement<'a>(
             ))
           }
         },
+        "const" => {
+          let origin = match doc.stack_get(doc_position) {
+            Some(&Part::Statement(s, e)) => format!("at {}", doc.locate(s, e).position()),
+            _ => "in the document".to_string(),
+          };
+          match resolve_statement_set(env, source, &mut iter, Some(origin)) {
+            Ok(_) => break,
+            Err(mut err) => {
+              return Err(add_step_internal_error!(
+                err,
+                "error in 'const' statement",
+                format!("source = '{}'", source.trim()),
+                "must be = 'const [symbol] = [text or symbol (+ text or symbol (+ ...))]'"
+              ))
+            }
+          }
+        }
+        "unset" => match resolve_statement_unset(env, source, &mut iter) {
+          Ok(_) => break,
+          Err(mut err) => {
+            return Err(add_step_internal_error!(
+              err,
+              "error in 'unset' statement",
+              format!("source = '{}'", source.trim()),
+              "must be = 'unset [symbol]' or 'unset block [text or symbol]'"
+            ))
+          }
+        },
         "find" => match resolve_statement_find(env, source, &mut iter) {
           Ok(_) => break,
           Err(mut err) => {
@@ -184,5 +294,5 @@ pub fn resolve_statement<'a>(
       }
     }
   }
-  Ok((output, position_skip))
+  Ok(output)
 }
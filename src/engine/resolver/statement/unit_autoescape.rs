@@ -0,0 +1,64 @@
+use core::iter::Peekable;
+use core::slice::Iter;
+
+use crate::create_internal_error;
+use crate::engine::document::Node;
+use crate::engine::resolver::resolve_nodes;
+use crate::engine::resolver::statement::is_pending;
+use crate::engine::resolver::statement::Token;
+use crate::engine::resolver::Part;
+use crate::engine::Document;
+use crate::engine::Environment;
+use crate::utils::conf::Escaping;
+use crate::utils::error::InternalError;
+
+pub fn resolve_unit<'a>(
+  doc: &'a Document,
+  children: &[Node],
+  env: &mut Environment,
+  source: &'a str,
+  iter_tokens: &mut Peekable<Iter<'_, Token>>,
+) -> Result<Vec<Part>, InternalError> {
+  let escaping: Escaping = loop {
+    match iter_tokens.next() {
+      Some(Token::Space(_)) => (),
+      Some(&Token::Symbol(s, e)) => match Escaping::parse(&source[s..e]) {
+        Ok(escaping) => break escaping,
+        Err(err) => return Err(create_internal_error!(err)),
+      },
+      Some(t) => {
+        return Err(create_internal_error!(format!(
+          "Found '{}' as escaping (must be Token::Symbol)",
+          t
+        )))
+      }
+      None => {
+        return Err(create_internal_error!(
+          "Statement must be complete (no escaping)"
+        ))
+      }
+    }
+  };
+  if let Some(t) = iter_tokens.find(|t| !matches!(t, Token::Space(_))) {
+    return Err(create_internal_error!(format!(
+      "Found '{}' after the escaping (nothing allowed)",
+      t
+    )));
+  }
+  let previous = env.set_escaping(escaping);
+  let mut changed: bool = false;
+  let body = resolve_nodes(doc, children, env, &mut changed);
+  let escaping = env.set_escaping(previous);
+  let body = body?;
+  if !is_pending(doc, &body) {
+    return Ok(body);
+  }
+  let mut result = vec![Part::GeneratedText(
+    doc
+      .delimiters
+      .scope(&format!("autoescape {}", escaping.name())),
+  )];
+  result.extend(body);
+  result.push(Part::GeneratedText(doc.delimiters.scope("endautoescape")));
+  Ok(result)
+}
@@ -5,17 +5,15 @@ use core::slice::Iter;
 use crate::create_internal_error;
 use crate::engine::resolver::statement::Token;
 use crate::engine::resolver::Part;
-use crate::engine::Document;
 use crate::engine::Environment;
 use crate::utils::error::InternalError;
 
 pub fn resolve_unit<'a>(
-  doc: &'a Document,
-  doc_position: usize,
+  body: &[Part],
   env: &mut Environment,
   source: &'a str,
   iter_tokens: &mut Peekable<Iter<'_, Token>>,
-) -> Result<usize, InternalError> {
+) -> Result<(), InternalError> {
   let block_name: String;
   loop {
     match iter_tokens.next() {
@@ -37,8 +35,8 @@ pub fn resolve_unit<'a>(
           };
           break;
         }
-        &Token::Text(s, e) => {
-          block_name = source[s..e].to_string();
+        Token::Text(_, _, text) => {
+          block_name = text.clone();
           break;
         }
         t => {
@@ -59,30 +57,6 @@ pub fn resolve_unit<'a>(
       ),
     };
   }
-  let mut iter_parts = doc.stack.iter().skip(doc_position).enumerate();
-  let mut block_ending_position: usize;
-  let mut i = 0;
-  loop {
-    let part = match iter_parts.next() {
-      Some((position, part)) => {
-        block_ending_position = position;
-        part
-      }
-      None => return Err(create_internal_error!("Unfinished 'block' in document")),
-    };
-    match part {
-      &Part::Statement(s, e) if doc.source[s + 2..e - 2].trim().starts_with("block") => i += 1,
-      &Part::Statement(s, e) if doc.source[s + 2..e - 2].trim() == "endblock" => {
-        i -= 1;
-        if i == 0 {
-          break;
-        }
-      }
-      _ => (),
-    }
-  }
-  let mut block: Vec<Part> = vec![];
-  block.extend_from_slice(&doc.stack[doc_position + 1..doc_position + block_ending_position]);
-  env.set_block(block_name, block);
-  return Ok(block_ending_position);
+  env.set_block(block_name, body.to_vec());
+  Ok(())
 }
@@ -22,17 +22,20 @@ pub fn resolve_unit<'a>(
           block_name = match env.get(&key) {
             Ok(Some(v)) => v.clone(),
             Ok(None) => {
-              return Err(create_internal_error!(format!(
-                "Undefined variable '{}' during retrieving the block name",
-                key
-              )))
+              return Err(
+                create_internal_error!(format!(
+                  "Undefined variable '{}' during retrieving the block name",
+                  key
+                ))
+                .with_undefined(key),
+              )
             }
             Err(err) => return Err(create_internal_error!(err)),
           };
           break;
         }
-        &Token::Text(s, e) => {
-          block_name = source[s..e].to_string();
+        Token::Text(_, _, text) => {
+          block_name = text.clone();
           break;
         }
         t => {
@@ -47,9 +50,9 @@ pub fn resolve_unit<'a>(
   }
   match env.get_block(&block_name) {
     Some(v) => Ok(v.clone()),
-    None => Err(create_internal_error!(format!(
-      "Undefined block '{}' in environment",
-      block_name
-    ))),
+    None => Err(
+      create_internal_error!(format!("Undefined block '{}' in environment", block_name))
+        .with_undefined(block_name),
+    ),
   }
 }
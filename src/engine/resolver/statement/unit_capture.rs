@@ -0,0 +1,82 @@
+use core::iter::Peekable;
+use core::slice::Iter;
+
+use crate::add_step_internal_error;
+use crate::create_internal_error;
+use crate::engine::document::Node;
+use crate::engine::extensions::Value;
+use crate::engine::renderer::render_passes;
+use crate::engine::resolver::resolve_nodes;
+use crate::engine::resolver::statement::Token;
+use crate::engine::resolver::Part;
+use crate::engine::Document;
+use crate::engine::Environment;
+use crate::utils::error::InternalError;
+
+fn render(
+  doc: &Document,
+  mut body: Document,
+  env: &mut Environment,
+) -> Result<String, InternalError> {
+  // the blocks hold positions in the document : they are written as text
+  // before being transformed with the positions of the body
+  env.transform(doc);
+  render_passes(&mut body, env, true)?;
+  Ok(body.source)
+}
+
+pub fn resolve_unit<'a>(
+  doc: &'a Document,
+  doc_position: usize,
+  children: &[Node],
+  env: &mut Environment,
+  source: &'a str,
+  iter_tokens: &mut Peekable<Iter<'_, Token>>,
+) -> Result<(), InternalError> {
+  let key: String = loop {
+    match iter_tokens.next() {
+      Some(Token::Space(_)) => (),
+      Some(&Token::Symbol(s, e)) => break source[s..e].to_string(),
+      Some(t) => {
+        return Err(create_internal_error!(format!(
+          "Found '{}' as variable name (must be Token::Symbol)",
+          t
+        )))
+      }
+      None => {
+        return Err(create_internal_error!(
+          "Statement must be complete (no variable name)"
+        ))
+      }
+    }
+  };
+  if let Some(t) = iter_tokens.find(|t| !matches!(t, Token::Space(_))) {
+    return Err(create_internal_error!(format!(
+      "Found '{}' after the variable name (nothing allowed)",
+      t
+    )));
+  }
+  let mut changed: bool = false;
+  let body = resolve_nodes(doc, children, env, &mut changed)?;
+  let producer = match doc.stack_get(doc_position) {
+    Some(&Part::Statement(s, e)) => (s, e),
+    _ => (0, 0),
+  };
+  let body = doc.nested(&body, producer);
+  let mut text = body.source.clone();
+  if doc.delimiters.is_opened_in(&text) {
+    text = match render(doc, body, env) {
+      Ok(t) => t,
+      Err(mut err) => {
+        return Err(add_step_internal_error!(
+          err,
+          "Error during rendering the captured body"
+        ))
+      }
+    };
+  }
+  match env.set(key, Value::Text(text)) {
+    Some(err) => Err(create_internal_error!(err)),
+    None => Ok(()),
+  }
+}
@@ -35,6 +35,12 @@ fn resolve_fct<'a>(
       }
     }
   }
+  while let Some(Token::Space(_)) = iter_tokens.peek() {
+    iter_tokens.next();
+  }
+  if !context.begining && matches!(iter_tokens.peek(), Some(Token::Pipe) | None) {
+    return call(context, fct, vec![]);
+  }
   loop {
     let token = match iter_tokens.next() {
       Some(t) => t,
@@ -64,8 +70,8 @@ fn resolve_fct<'a>(
       &Token::Symbol(s, e) => {
         args.push(Value::Symbol(context.source[s..e].to_string()));
       }
-      &Token::Text(s, e) => {
-        args.push(Value::Text(context.source[s..e].to_string()));
+      Token::Text(_, _, text) => {
+        args.push(Value::Text(text.clone()));
       }
       &Token::ParenthesisEnding => break,
       t => {
@@ -76,6 +82,10 @@ fn resolve_fct<'a>(
       }
     }
   }
+  call(context, fct, args)
+}
+
+fn call<'a>(context: &mut Context<'a>, fct: &'a str, args: Vec<Value>) -> Option<String> {
   let f: Vec<&str> = fct.splitn(2, '.').collect();
   if f.len() < 2 {
     return Some(format!(
@@ -112,8 +122,9 @@ fn cast(env: &mut Environment, results: Option<Value>) -> Result<String, String>
       }
       return Ok(tmp.join("\n"));
     }
+    Some(Value::Map(map)) => return Ok(Value::Map(map).to_string()),
     Some(Value::True) => return Ok("true".to_string()),
-    Some(Value::Number(n)) => return Ok(n.to_string()),
+    Some(n @ (Value::Integer(_) | Value::Number(_))) => return Ok(n.to_string()),
     Some(Value::False) => return Ok("".to_string()),
     Some(Value::Void) => return Ok("".to_string()),
     None => return Ok("".to_string()),
@@ -178,8 +189,70 @@ pub fn resolve_unit<'a>(
     }
   }
   let mut context = Context::new(doc, doc_position, env, source);
+  call_functions(&mut context, iter_tokens)?;
+  let Context {
+    begining: _,
+    result,
+    doc: _,
+    doc_position: _,
+    env,
+    source: _,
+    fct_name: _,
+    args: _,
+  } = context;
+  let value = match cast(env, result) {
+    Ok(value) => value,
+    Err(err) => {
+      let mut err = create_internal_error!(err);
+      return Err(add_step_internal_error!(
+        err,
+        "Error during casting of the final function return"
+      ));
+    }
+  };
+  match env.set(key, value) {
+    Some(err) => Err(create_internal_error!(err)),
+    None => Ok(()),
+  }
+}
+
+pub fn resolve_filters<'a>(
+  doc: &'a Document,
+  doc_position: usize,
+  env: &mut Environment,
+  source: &'a str,
+  value: String,
+  iter_tokens: &mut Peekable<Iter<'_, Token>>,
+) -> Result<String, InternalError> {
+  if doc.conf.no_extensions {
+    return Err(create_internal_error!(
+      "Filter found in expression: not authorized by configuration",
+      "the --no-extensions argument was specified"
+    ));
+  }
+  let mut context = Context::new(doc, doc_position, env, source);
+  context.begining = false;
+  context.result = Some(Value::Text(value));
+  call_functions(&mut context, iter_tokens)?;
+  let Context { result, env, .. } = context;
+  match cast(env, result) {
+    Ok(value) => Ok(value),
+    Err(err) => {
+      let mut err = create_internal_error!(err);
+      Err(add_step_internal_error!(
+        err,
+        "Error during casting of the final filter return"
+      ))
+    }
+  }
+}
+
+fn call_functions(
+  context: &mut Context,
+  iter_tokens: &mut Peekable<Iter<'_, Token>>,
+) -> Result<(), InternalError> {
   'outer: loop {
-    match resolve_fct(&mut context, iter_tokens) {
+    match resolve_fct(context, iter_tokens) {
       Some(err) => {
         let mut err = create_internal_error!(err);
         return Err(add_step_internal_error!(
@@ -208,28 +281,5 @@ pub fn resolve_unit<'a>(
       }
     }
   }
-  let Context {
-    begining: _,
-    result,
-    doc: _,
-    doc_position: _,
-    env,
-    source: _,
-    fct_name: _,
-    args: _,
-  } = context;
-  let value = match cast(env, result) {
-    Ok(value) => value,
-    Err(err) => {
-      let mut err = create_internal_error!(err);
-      return Err(add_step_internal_error!(
-        err,
-        "Error during casting of the final function return"
-      ));
-    }
-  };
-  match env.set(key, value) {
-    Some(err) => Err(create_internal_error!(err)),
-    None => Ok(()),
-  }
+  Ok(())
 }
@@ -4,6 +4,7 @@ use std::iter::Peekable;
 use std::path::Path;
 
 use crate::create_internal_error;
+use crate::engine::extensions::Value;
 use crate::engine::resolver::statement::Token;
 use crate::engine::Environment;
 use crate::utils::error::InternalError;
@@ -77,7 +78,7 @@ pub fn resolve_unit<'a>(
       }
     }
   }
-  let pattern: &str;
+  let pattern: String;
   loop {
     let token = match iter_tokens.next() {
       Some(t) => t,
@@ -103,8 +104,8 @@ pub fn resolve_unit<'a>(
         };
         break;
       }
-      &Token::Text(s, e) => {
-        pattern = &source[s..e];
+      Token::Text(_, _, text) => {
+        pattern = text.clone();
         break;
       }
       t => {
@@ -185,7 +186,7 @@ pub fn resolve_unit<'a>(
       }
     }
   }
-  let mut join_char: &str = "\n";
+  let mut join_char: Option<String> = None;
   if optional_part {
     join_char = loop {
       let token = match iter_tokens.next() {
@@ -197,7 +198,7 @@ pub fn resolve_unit<'a>(
         &Token::Symbol(s, e) => {
           let key = source[s..e].to_string();
           match env.get(&key) {
-            Ok(Some(v)) => break v,
+            Ok(Some(v)) => break Some(v),
             Ok(None) => {
               return Err(create_internal_error!(format!(
                 "Undefined variable '{}' as join char",
@@ -207,7 +208,7 @@ pub fn resolve_unit<'a>(
             Err(err) => return Err(create_internal_error!(err)),
           }
         }
-        &Token::Text(s, e) => break &source[s..e],
+        Token::Text(_, _, text) => break Some(text.clone()),
         t => {
           return Err(create_internal_error!(format!(
             "Found '{}' in first part (must be Token::Symbol or Token::Text)",
@@ -229,8 +230,9 @@ pub fn resolve_unit<'a>(
     }
     Path::new(left)
   } else {
-    Path::new(pattern)
+    Path::new(&pattern)
   };
+  let dependency = path.display().to_string();
   if path.is_file() {
     match env.set(destination, pattern.to_string()) {
       Some(err) => return Err(create_internal_error!(err)),
@@ -266,7 +268,13 @@ pub fn resolve_unit<'a>(
       }
       None => (),
     }
-    match env.set(destination, results.join(join_char)) {
+    // the order of read_dir depends on the file system
+    results.sort();
+    let value = match join_char {
+      Some(join_char) => Value::Text(results.join(&join_char)),
+      None => Value::Vector(results.into_iter().map(Value::Text).collect()),
+    };
+    match env.set(destination, value) {
       Some(err) => return Err(create_internal_error!(err)),
       None => (),
     }
@@ -276,5 +284,6 @@ pub fn resolve_unit<'a>(
       pattern
     )));
   }
+  env.add_dependency(dependency);
   Ok(())
 }
@@ -2,6 +2,9 @@ use core::iter::Peekable;
 use core::slice::Iter;
 
 use crate::create_internal_error;
+use crate::engine::extensions::Value;
+use crate::engine::resolver::statement::is_pending;
+use crate::engine::resolver::statement::unit_with::literal;
 use crate::engine::resolver::statement::Token;
 use crate::engine::resolver::Part;
 use crate::engine::Document;
@@ -10,33 +13,11 @@ use crate::utils::error::InternalError;
 
 pub fn resolve_unit<'a>(
   doc: &'a Document,
-  doc_position: usize,
+  body: &[Part],
   env: &mut Environment,
   source: &'a str,
   iter_tokens: &mut Peekable<Iter<'_, Token>>,
-) -> Result<(Vec<Part>, usize), InternalError> {
-  let mut iter_parts = doc.stack.iter().skip(doc_position).enumerate();
-  let mut block_ending_position: usize;
-  let mut i = 0;
-  loop {
-    let part = match iter_parts.next() {
-      Some((position, part)) => {
-        block_ending_position = position;
-        part
-      }
-      None => return Err(create_internal_error!("Unfinished block 'for'")),
-    };
-    match part {
-      &Part::Statement(s, e) if doc.source[s + 2..e - 2].trim().starts_with("for") => i += 1,
-      &Part::Statement(s, e) if doc.source[s + 2..e - 2].trim() == "endfor" => {
-        i -= 1;
-        if i == 0 {
-          break;
-        }
-      }
-      _ => (),
-    }
-  }
+) -> Result<Vec<Part>, InternalError> {
   let destination: &str = loop {
     let token = match iter_tokens.next() {
       Some(t) => t,
@@ -83,7 +64,7 @@ pub fn resolve_unit<'a>(
       }
     }
   }
-  let list: String = loop {
+  let list: Value = loop {
     let token = match iter_tokens.next() {
       Some(t) => t,
       None => {
@@ -96,18 +77,18 @@ pub fn resolve_unit<'a>(
       Token::Space(_) => (),
       &Token::Symbol(s, e) => {
         let key: String = source[s..e].to_string();
-        match env.get(&key) {
-          Ok(Some(v)) => break v.to_string(),
+        match env.get_value(&key) {
+          Ok(Some(v)) => break v.clone(),
           Ok(None) => {
-            return Err(create_internal_error!(format!(
-              "Undefined variable '{}' as pattern",
-              key
-            )))
+            return Err(
+              create_internal_error!(format!("Undefined variable '{}' as pattern", key))
+                .with_undefined(key),
+            )
           }
           Err(err) => return Err(create_internal_error!(err)),
         };
       }
-      &Token::Text(s, e) => break (&source[s..e]).to_string(),
+      Token::Text(_, _, text) => break Value::Text(text.clone()),
       t => {
         return Err(create_internal_error!(format!(
           "Found '{}' in third part (must be Token::Symbol['to'])",
@@ -136,7 +117,7 @@ pub fn resolve_unit<'a>(
       }
     }
   }
-  let mut split_char: &str = "\n";
+  let mut split_char: Option<String> = None;
   if optional_part {
     split_char = loop {
       let token = match iter_tokens.next() {
@@ -148,17 +129,17 @@ pub fn resolve_unit<'a>(
         &Token::Symbol(s, e) => {
           let key = source[s..e].to_string();
           match env.get(&key) {
-            Ok(Some(v)) => break v,
+            Ok(Some(v)) => break Some(v),
             Ok(None) => {
-              return Err(create_internal_error!(format!(
-                "Undefined variable '{}' as split char",
-                key
-              )))
+              return Err(
+                create_internal_error!(format!("Undefined variable '{}' as split char", key))
+                  .with_undefined(key),
+              )
             }
             Err(err) => return Err(create_internal_error!(err)),
           }
         }
-        &Token::Text(s, e) => break &source[s..e],
+        Token::Text(_, _, text) => break Some(text.clone()),
         t => {
           return Err(create_internal_error!(format!(
             "Found '{}' in first part (must be Token::Symbol or Token::Text)",
@@ -168,15 +149,36 @@ pub fn resolve_unit<'a>(
       }
     };
   }
+  let items: Vec<Value> = match (list, split_char) {
+    (Value::Vector(items), None) => items,
+    (list, split_char) => list
+      .to_string()
+      .split(split_char.as_deref().unwrap_or("\n"))
+      .map(Value::from)
+      .collect(),
+  };
   let mut results: Vec<Part> = vec![];
-  for item in list.split(split_char) {
-    let mut result: Vec<Part> = vec![Part::GeneratedText(format!(
-      "{{% set {} = \"{}\" %}}",
-      destination,
-      item.replace("\"", "\\\"")
-    ))];
-    result.extend(doc.stack[doc_position + 1..doc_position + block_ending_position].to_vec());
-    results.extend(result);
+  let pending = is_pending(doc, body);
+  for item in items {
+    if !doc.reentrant {
+      // no following pass : the item is only written
+      results.push(Part::GeneratedText(doc.delimiters.statement(&format!(
+        "set {} = {}",
+        destination,
+        literal(&item)
+      ))));
+      results.extend(body.to_vec());
+    } else if pending {
+      results.push(Part::GeneratedText(doc.delimiters.scope(&format!(
+        "with {} = {}",
+        destination,
+        literal(&item)
+      ))));
+      results.extend(body.to_vec());
+      results.push(Part::GeneratedText(doc.delimiters.scope("endwith")));
+    } else {
+      results.extend(body.to_vec());
+    }
   }
-  Ok((results, block_ending_position))
+  Ok(results)
 }
@@ -5,7 +5,6 @@ use crate::add_step_internal_error;
 use crate::create_internal_error;
 use crate::engine::resolver::statement::Token;
 use crate::engine::resolver::Part;
-use crate::engine::Document;
 use crate::engine::Environment;
 use crate::utils::error::InternalError;
 
@@ -28,10 +27,15 @@ impl Condition {
 enum ConditionPart {
   // Assertion :
   //  - is equal (bool)
-  //  - is symbol (bool), start (usize), end (usize) -> left
-  //  - is symbol (bool), start (usize), end (usize) -> right
-  Assertion(bool, bool, usize, usize, bool, usize, usize),
-  Text(usize, usize),
+  //  - is symbol (bool), name or text (String) -> left
+  //  - is symbol (bool), name or text (String) -> right
+  Assertion(bool, bool, String, bool, String),
+  // Definition ('defined x', 'not defined block "x"') :
+  //  - is negated (bool)
+  //  - is block (bool)
+  //  - is symbol (bool), name or text (String) -> name
+  Definition(bool, bool, bool, String),
+  Text(String),
   Symbol(usize, usize),
   EqualComparator,
   NonEqualComparator,
@@ -90,10 +94,10 @@ fn exp_symbol_or_text<'a>(
     ResultTokenPosition::False => (),
     ResultTokenPosition::Error(err) => return ResultTokenPosition::Error(err),
   }
-  match terminal(true, Token::Text(0, 0), tokens, position) {
+  match terminal(true, Token::Text(0, 0, String::new()), tokens, position) {
     ResultTokenPosition::True(p) => match tokens.get(p) {
-      Some(Token::Text(s, e)) => {
-        condition.tmp.push(ConditionPart::Text(*s, *e));
+      Some(Token::Text(_, _, text)) => {
+        condition.tmp.push(ConditionPart::Text(text.clone()));
         return ResultTokenPosition::True(p);
       }
       Some(t) => {
@@ -191,11 +195,92 @@ fn exp_comparator<'a>(
   }
 }
 
+fn next_not_space<'a>(tokens: &Vec<&'a Token>, mut position: usize) -> Option<(usize, &'a Token)> {
+  loop {
+    match tokens.get(position) {
+      Some(Token::Space(_)) => (),
+      Some(t) => return Some((position, *t)),
+      None => return None,
+    }
+    position += 1;
+  }
+}
+
+fn exp_definition<'a>(
+  condition: &mut Condition,
+  source: &'a str,
+  tokens: &Vec<&'a Token>,
+  position: usize,
+) -> ResultTokenPosition {
+  let symbol = |t: &Token| match *t {
+    Token::Symbol(s, e) => Some(&source[s..e]),
+    _ => None,
+  };
+  let (mut p, token) = match next_not_space(tokens, position) {
+    Some(n) => n,
+    None => return ResultTokenPosition::False,
+  };
+  let negated = symbol(token) == Some("not");
+  if negated {
+    p = match next_not_space(tokens, p + 1) {
+      Some((p, t)) if symbol(t) == Some("defined") => p,
+      _ => return ResultTokenPosition::False,
+    };
+  } else if symbol(token) != Some("defined") {
+    return ResultTokenPosition::False;
+  }
+  let (mut p, mut token) = match next_not_space(tokens, p + 1) {
+    Some((p, t)) if symbol(t).is_some() => (p, t),
+    _ if negated => {
+      return ResultTokenPosition::Error(
+        "symbol or 'block' expected after 'not defined'".to_string(),
+      )
+    }
+    _ => return ResultTokenPosition::False,
+  };
+  let mut is_block = false;
+  if symbol(token) == Some("block") {
+    if let Some((next_p, next_token)) = next_not_space(tokens, p + 1) {
+      if matches!(next_token, Token::Symbol(_, _) | Token::Text(_, _, _)) {
+        is_block = true;
+        p = next_p;
+        token = next_token;
+      }
+    }
+  }
+  match token {
+    &Token::Symbol(s, e) => condition.parts.push(ConditionPart::Definition(
+      negated,
+      is_block,
+      true,
+      source[s..e].to_string(),
+    )),
+    Token::Text(_, _, text) => condition.parts.push(ConditionPart::Definition(
+      negated,
+      is_block,
+      false,
+      text.clone(),
+    )),
+    _ => {
+      return ResultTokenPosition::Error(format!(
+        "Invalid token found : {} (must be a symbol or text)",
+        token
+      ))
+    }
+  }
+  ResultTokenPosition::True(p)
+}
+
 fn exp_assertion<'a>(
   condition: &mut Condition,
+  source: &'a str,
   tokens: &Vec<&'a Token>,
   mut position: usize,
 ) -> ResultTokenPosition {
+  match exp_definition(condition, source, tokens, position) {
+    ResultTokenPosition::False => (),
+    r => return r,
+  }
   match exp_symbol_or_text(condition, tokens, position) {
     ResultTokenPosition::True(p) => position = p + 1,
     ResultTokenPosition::False => return ResultTokenPosition::False,
@@ -219,9 +304,9 @@ fn exp_assertion<'a>(
     }
     ResultTokenPosition::Error(err) => return ResultTokenPosition::Error(err),
   };
-  let (second_is_symbol, second_start, second_end) = match condition.tmp.pop() {
-    Some(ConditionPart::Text(s,e)) => (false, s, e),
-    Some(ConditionPart::Symbol(s,e)) => (true, s, e),
+  let (second_is_symbol, second) = match condition.tmp.pop() {
+    Some(ConditionPart::Text(text)) => (false, text),
+    Some(ConditionPart::Symbol(s,e)) => (true, source[s..e].to_string()),
     o => return ResultTokenPosition::Error(format!("internal logic error in tmp condition (found '{:?}', must be ConditionPart::[Text or Symbol])", o)),
   };
   let comparator = match condition.tmp.pop() {
@@ -229,29 +314,28 @@ fn exp_assertion<'a>(
     Some(ConditionPart::NonEqualComparator) => false,
     o => return ResultTokenPosition::Error(format!("internal logic error in tmp condition (found '{:?}', must be ConditionPart::[EqualComparator or NonEqualComparator])", o)),
   };
-  let (first_is_symbol, first_start, first_end) = match condition.tmp.pop() {
-    Some(ConditionPart::Text(s,e)) => (false, s, e),
-    Some(ConditionPart::Symbol(s,e)) => (true, s, e),
+  let (first_is_symbol, first) = match condition.tmp.pop() {
+    Some(ConditionPart::Text(text)) => (false, text),
+    Some(ConditionPart::Symbol(s,e)) => (true, source[s..e].to_string()),
     o => return ResultTokenPosition::Error(format!("internal logic error in tmp condition (found '{:?}', must be ConditionPart::[Text or Symbol])", o)),
   };
   condition.parts.push(ConditionPart::Assertion(
     comparator,
     first_is_symbol,
-    first_start,
-    first_end,
+    first,
     second_is_symbol,
-    second_start,
-    second_end,
+    second,
   ));
   ResultTokenPosition::True(p)
 }
 
 fn exp_assertions<'a>(
   condition: &mut Condition,
+  source: &'a str,
   tokens: &Vec<&'a Token>,
   mut position: usize,
 ) -> ResultTokenPosition {
-  match exp_assertion(condition, tokens, position) {
+  match exp_assertion(condition, source, tokens, position) {
     ResultTokenPosition::True(p) => position = p + 1,
     ResultTokenPosition::False => return ResultTokenPosition::False,
     ResultTokenPosition::Error(err) => return ResultTokenPosition::Error(err),
@@ -262,7 +346,7 @@ fn exp_assertions<'a>(
       ResultTokenPosition::False => break,
       ResultTokenPosition::Error(err) => return ResultTokenPosition::Error(err),
     }
-    match exp_group_or_assertions(condition, tokens, position) {
+    match exp_group_or_assertions(condition, source, tokens, position) {
       ResultTokenPosition::True(p) => position = p + 1,
       ResultTokenPosition::False => return ResultTokenPosition::False,
       ResultTokenPosition::Error(err) => return ResultTokenPosition::Error(err),
@@ -273,6 +357,7 @@ fn exp_assertions<'a>(
 
 fn exp_group<'a>(
   condition: &mut Condition,
+  source: &'a str,
   tokens: &Vec<&'a Token>,
   mut position: usize,
 ) -> ResultTokenPosition {
@@ -287,7 +372,7 @@ fn exp_group<'a>(
     ResultTokenPosition::False => return ResultTokenPosition::False,
     ResultTokenPosition::Error(err) => return ResultTokenPosition::Error(err),
   }
-  match exp_general(condition, tokens, position) {
+  match exp_general(condition, source, tokens, position) {
     ResultTokenPosition::True(p) => position = p + 1,
     ResultTokenPosition::False => return ResultTokenPosition::False,
     ResultTokenPosition::Error(err) => {
@@ -317,15 +402,16 @@ fn exp_group<'a>(
 
 fn exp_group_or_assertions<'a>(
   condition: &mut Condition,
+  source: &'a str,
   tokens: &Vec<&'a Token>,
   position: usize,
 ) -> ResultTokenPosition {
-  match exp_group(condition, tokens, position) {
+  match exp_group(condition, source, tokens, position) {
     ResultTokenPosition::True(p) => return ResultTokenPosition::True(p),
     ResultTokenPosition::False => (),
     ResultTokenPosition::Error(err) => return ResultTokenPosition::Error(err),
   }
-  match exp_assertions(condition, tokens, position) {
+  match exp_assertions(condition, source, tokens, position) {
     ResultTokenPosition::True(p) => return ResultTokenPosition::True(p),
     ResultTokenPosition::False => return ResultTokenPosition::False,
     ResultTokenPosition::Error(err) => return ResultTokenPosition::Error(err),
@@ -334,10 +420,11 @@ fn exp_group_or_assertions<'a>(
 
 fn exp_general<'a>(
   condition: &mut Condition,
+  source: &'a str,
   tokens: &Vec<&'a Token>,
   mut position: usize,
 ) -> ResultTokenPosition {
-  match exp_group_or_assertions(condition, tokens, position) {
+  match exp_group_or_assertions(condition, source, tokens, position) {
     ResultTokenPosition::True(p) => position = p + 1,
     ResultTokenPosition::False => return ResultTokenPosition::False,
     ResultTokenPosition::Error(err) => return ResultTokenPosition::Error(err),
@@ -348,7 +435,7 @@ fn exp_general<'a>(
       ResultTokenPosition::False => break,
       ResultTokenPosition::Error(err) => return ResultTokenPosition::Error(err),
     }
-    match exp_group_or_assertions(condition, tokens, position) {
+    match exp_group_or_assertions(condition, source, tokens, position) {
       ResultTokenPosition::True(p) => position = p + 1,
       ResultTokenPosition::False => {
         return ResultTokenPosition::Error(format!(
@@ -362,7 +449,7 @@ fn exp_general<'a>(
   ResultTokenPosition::True(position)
 }
 
-fn verify_tokens<'a>(tokens: Vec<&Token>) -> Result<Condition, InternalError> {
+fn verify_tokens<'a>(source: &'a str, tokens: Vec<&'a Token>) -> Result<Condition, InternalError> {
   if tokens.len() == 0 {
     return Err(create_internal_error!(
       "The condition is empty (no assertion provided)"
@@ -370,25 +457,44 @@ fn verify_tokens<'a>(tokens: Vec<&Token>) -> Result<Condition, InternalError> {
   }
   let position = 0;
   let mut condition = Condition::new();
-  return match exp_general(&mut condition, &tokens, position) {
+  return match exp_general(&mut condition, source, &tokens, position) {
     ResultTokenPosition::True(_) => Ok(condition),
     ResultTokenPosition::False => Err(create_internal_error!("The condition is invalid")),
     ResultTokenPosition::Error(err) => Err(create_internal_error!(err)),
   };
 }
 
-fn resolve_exp<'a>(
+fn combine(result: &mut bool, operator_and: &mut Option<bool>, r: bool) {
+  match *operator_and {
+    Some(true) => *result &= r,
+    Some(false) => *result |= r,
+    None => *result = r,
+  }
+  *operator_and = None;
+}
+
+// the result is already known : false before '&&', true before '||'
+fn is_short_circuited(result: bool, operator_and: Option<bool>) -> bool {
+  match operator_and {
+    Some(true) => !result,
+    Some(false) => result,
+    None => false,
+  }
+}
+
+// without evaluation, the assertions of the group are only skipped
+fn resolve_exp(
   env: &Environment,
-  source: &'a str,
   condition: &Condition,
   mut position: usize,
+  evaluation: bool,
 ) -> Result<(bool, usize), InternalError> {
   let mut result = false;
   let mut operator_and: Option<bool> = None;
   let mut beginning = true;
   loop {
     match condition.parts.get(position) {
-      Some(ConditionPart::Text(_, _)) | Some(ConditionPart::Symbol(_, _)) => {
+      Some(ConditionPart::Text(_)) | Some(ConditionPart::Symbol(_, _)) => {
         return Err(create_internal_error!(
           "The expression resolver encountered invalid logic in the condition",
           "Expecting : ConditionPart::[Text or Symbol]"
@@ -420,7 +526,8 @@ fn resolve_exp<'a>(
           ));
         }
       }
-      Some(ConditionPart::Assertion(_, _, _, _, _, _, _))
+      Some(ConditionPart::Assertion(_, _, _, _, _))
+      | Some(ConditionPart::Definition(_, _, _, _))
         if operator_and == None && beginning == false =>
       {
         return Err(create_internal_error!(
@@ -428,46 +535,50 @@ fn resolve_exp<'a>(
           "No operator found between two assertions"
         ))
       }
+      Some(ConditionPart::Assertion(_, _, _, _, _))
+      | Some(ConditionPart::Definition(_, _, _, _))
+        if !evaluation || is_short_circuited(result, operator_and) =>
+      {
+        operator_and = None
+      }
       Some(ConditionPart::Assertion(
         is_equal,
         first_is_symbol,
-        first_start,
-        first_end,
+        first_value,
         second_is_symbol,
-        second_start,
-        second_end,
+        second_value,
       )) => {
         let first: String;
         if *first_is_symbol {
-          let key: String = source[*first_start..*first_end].to_string();
+          let key: String = first_value.to_string();
           match env.get(&key) {
             Ok(Some(v)) => first = v.to_string(),
             Ok(None) => {
-              return Err(create_internal_error!(format!(
-                "Undefined variable '{}' in condition",
-                key
-              )))
+              return Err(
+                create_internal_error!(format!("Undefined variable '{}' in condition", key))
+                  .with_undefined(key),
+              )
             }
             Err(err) => return Err(create_internal_error!(err)),
           }
         } else {
-          first = source[*first_start..*first_end].to_string();
+          first = first_value.to_string();
         }
         let second: String;
         if *second_is_symbol {
-          let key: String = source[*second_start..*second_end].to_string();
+          let key: String = second_value.to_string();
           match env.get(&key) {
             Ok(Some(v)) => second = v.to_string(),
             Ok(None) => {
-              return Err(create_internal_error!(format!(
-                "Undefined variable '{}' in condition",
-                key
-              )))
+              return Err(
+                create_internal_error!(format!("Undefined variable '{}' in condition", key))
+                  .with_undefined(key),
+              )
             }
             Err(err) => return Err(create_internal_error!(err)),
           }
         } else {
-          second = source[*second_start..*second_end].to_string();
+          second = second_value.to_string();
         }
         let r: bool;
         if *is_equal {
@@ -475,105 +586,98 @@ fn resolve_exp<'a>(
         } else {
           r = first != second;
         }
-        if operator_and == Some(true) {
-          result &= r;
-          operator_and = None;
-        } else if operator_and == Some(false) {
-          result |= r;
-          operator_and = None;
-        } else {
-          result = r;
+        combine(&mut result, &mut operator_and, r);
+      }
+      Some(&ConditionPart::Definition(is_negated, is_block, is_symbol, ref value)) => {
+        let mut name: String = value.to_string();
+        if is_block && is_symbol {
+          name = match env.get(&name) {
+            Ok(Some(v)) => v.to_string(),
+            Ok(None) => {
+              return Err(
+                create_internal_error!(format!(
+                  "Undefined variable '{}' during retrieving the block name",
+                  name
+                ))
+                .with_undefined(name),
+              )
+            }
+            Err(err) => return Err(create_internal_error!(err)),
+          };
         }
+        let defined = if is_block {
+          env.has_block(&name)
+        } else {
+          env.is_defined(&name)
+        };
+        combine(&mut result, &mut operator_and, defined != is_negated);
       }
       Some(ConditionPart::GroupOpening) => {
-        match resolve_exp(env, source, condition, position + 1) {
-          Ok((r, p)) => {
-            position = p;
-            if operator_and == Some(true) {
-              result &= r;
-            } else {
-              return Ok((result, position + 1));
-            }
-          }
-          Err(err) => return Err(err),
+        if operator_and.is_none() && !beginning {
+          return Err(create_internal_error!(
+            "Invalid logic in expression resolver in condition",
+            "No operator found between two assertions"
+          ));
+        }
+        let skipped = !evaluation || is_short_circuited(result, operator_and);
+        let (r, p) = resolve_exp(env, condition, position + 1, !skipped)?;
+        position = p;
+        match skipped {
+          true => operator_and = None,
+          false => combine(&mut result, &mut operator_and, r),
         }
       }
-      Some(ConditionPart::GroupEnding) | None => return Ok((result, position + 1)),
+      Some(ConditionPart::GroupEnding) | None => return Ok((result, position)),
     }
     position += 1;
     beginning = false;
   }
 }
 
-fn resolve_condition<'a>(
-  env: &Environment,
-  source: &'a str,
-  condition: Condition,
-) -> Result<bool, InternalError> {
-  match resolve_exp(env, source, &condition, 0) {
+fn resolve_condition(env: &Environment, condition: Condition) -> Result<bool, InternalError> {
+  match resolve_exp(env, &condition, 0, true) {
     Ok((r, _)) => Ok(r),
     Err(err) => Err(err),
   }
 }
 
-pub fn resolve_unit<'a>(
-  doc: &'a Document,
-  doc_position: usize,
-  env: &mut Environment,
+pub fn evaluate_condition<'a>(
+  env: &Environment,
   source: &'a str,
-  iter_tokens: &mut Peekable<Iter<'_, Token>>,
-) -> Result<(Vec<Part>, usize), InternalError> {
-  let mut iter_parts = doc.stack.iter().skip(doc_position).enumerate();
-  let mut block_ending_position: usize;
-  let mut i = 0;
-  loop {
-    let part = match iter_parts.next() {
-      Some((position, part)) => {
-        block_ending_position = position;
-        part
-      }
-      None => return Err(create_internal_error!("Unfinished block 'if'")),
-    };
-    match part {
-      &Part::Statement(s, e) if doc.source[s + 2..e - 2].trim().starts_with("if") => i += 1,
-      &Part::Statement(s, e) if doc.source[s + 2..e - 2].trim() == "endif" => {
-        i -= 1;
-        if i == 0 {
-          break;
-        }
-      }
-      _ => (),
-    }
-  }
-  let tokens = iter_tokens.collect::<Vec<&Token>>();
-  let condition = match verify_tokens(tokens) {
+  tokens: Vec<&'a Token>,
+) -> Result<bool, InternalError> {
+  let condition = match verify_tokens(source, tokens) {
     Ok(c) => c,
     Err(mut err) => {
       return Err(add_step_internal_error!(
         err,
         "Error during conditional tokens parsing ('verify tokens' step)",
-        "must be = '\x1b[3mif [symbol or text] ['==' or '!='] [symbol or text] ( ['&&' or '||'] ... )\x1b[0m'",
-        format!("found statement = '\x1b[3m{}\x1b[0m'", source.trim())
-      ))
-    }
-  };
-  let result = match resolve_condition(env, source, condition) {
-    Ok(r) => r,
-    Err(mut err) => {
-      return Err(add_step_internal_error!(
-        err,
-        "Error during conditional tokens resolving ('resolve condition' step)",
-        "must be = '\x1b[3mif [symbol or text] [ '==' | '!=' ] [symbol or text] ( [ '&' | '|' ] ... )\x1b[0m'",
+        "must be = '\x1b[3mif [symbol or text] ['==' or '!='] [symbol or text] ( ['&&' or '||'] ... )\x1b[0m' (an assertion can be '(not) defined [symbol]' or '(not) defined block [text or symbol]')",
         format!("found statement = '\x1b[3m{}\x1b[0m'", source.trim())
       ))
     }
   };
-  if result {
-    Ok((
-      doc.stack[doc_position + 1..doc_position + block_ending_position - 1].to_vec(),
-      block_ending_position,
-    ))
+  match resolve_condition(env, condition) {
+    Ok(r) => Ok(r),
+    Err(mut err) => Err(add_step_internal_error!(
+      err,
+      "Error during conditional tokens resolving ('resolve condition' step)",
+      "must be = '\x1b[3mif [symbol or text] [ '==' | '!=' ] [symbol or text] ( [ '&' | '|' ] ... )\x1b[0m'",
+      format!("found statement = '\x1b[3m{}\x1b[0m'", source.trim())
+    )),
+  }
+}
+
+pub fn resolve_unit<'a>(
+  body: &[Part],
+  env: &mut Environment,
+  source: &'a str,
+  iter_tokens: &mut Peekable<Iter<'_, Token>>,
+) -> Result<Vec<Part>, InternalError> {
+  let tokens = iter_tokens.collect::<Vec<&Token>>();
+  if evaluate_condition(env, source, tokens)? {
+    Ok(body.to_vec())
   } else {
-    Ok((vec![], block_ending_position))
+    Ok(vec![])
   }
 }
@@ -19,8 +19,8 @@ pub fn resolve_unit<'a>(
     match iter_tokens.next() {
       Some(token) => match token {
         Token::Space(_) => (),
-        &Token::Text(s, e) => {
-          include_path = source[s..e].to_string();
+        Token::Text(_, _, text) => {
+          include_path = text.clone();
           break;
         }
         &Token::Symbol(s, e) => {
@@ -28,10 +28,10 @@ pub fn resolve_unit<'a>(
           include_path = match env.get(&value) {
             Ok(Some(v)) => v.clone(),
             Ok(None) => {
-              return Err(create_internal_error!(format!(
-                "Undefined variable '{}'",
-                value
-              )))
+              return Err(
+                create_internal_error!(format!("Undefined variable '{}'", value))
+                  .with_undefined(value),
+              )
             }
             Err(err) => return Err(create_internal_error!(err)),
           };
@@ -47,6 +47,7 @@ pub fn resolve_unit<'a>(
       None => return Err(create_internal_error!("Unterminated block declaration.")),
     }
   }
+  env.add_dependency(include_path.clone());
   let path = Path::new(&include_path);
   if !path.exists() {
     return Err(create_internal_error!(
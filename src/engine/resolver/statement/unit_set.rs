@@ -2,6 +2,7 @@ use core::slice::Iter;
 use std::iter::Peekable;
 
 use crate::create_internal_error;
+use crate::engine::resolver::expression::evaluate;
 use crate::engine::resolver::statement::Token;
 use crate::engine::Environment;
 use crate::utils::error::InternalError;
@@ -10,8 +11,9 @@ pub fn resolve_unit<'a>(
   env: &mut Environment,
   source: &'a str,
   iter_tokens: &mut Peekable<Iter<'_, Token>>,
+  constant: Option<String>,
 ) -> Result<(), InternalError> {
-  let mut key: String;
+  let key: String;
   loop {
     let token = match iter_tokens.next() {
       Some(t) => t,
@@ -51,97 +53,35 @@ pub fn resolve_unit<'a>(
       }
     }
   }
-  let mut value: Vec<String> = vec![];
-  let mut begining: bool = true;
-  let mut operator: bool = false;
+  let mut value_tokens: Vec<&Token> = vec![];
   let mut if_part: bool = false;
-  loop {
-    let token = match iter_tokens.next() {
-      Some(t) => t,
-      None => {
-        if begining {
-          return Err(create_internal_error!("The second part cannot be empty"));
-        } else if operator == false {
-          return Err(create_internal_error!(
-            "Invalid ending : an operator without symbol or text after"
-          ));
-        } else {
-          break;
-        }
-      }
-    };
-    begining = false;
+  for token in iter_tokens.by_ref() {
     match token {
       Token::Space(_) => (),
-      &Token::Symbol(s, e) => {
-        key = source[s..e].to_string();
-        if operator == false {
-          match env.get(&key) {
-            Ok(Some(v)) => value.push(v.clone()),
-            Ok(None) => {
-              return Err(create_internal_error!(format!(
-                "Undefined variable '{}' (position {} ~> {})",
-                key, s, e
-              )))
-            }
-            Err(err) => return Err(create_internal_error!(err)),
-          }
-          operator = true;
-        } else {
-          return Err(create_internal_error!(
-            "Operator missing between symbol or text",
-            format!("error on symbol : '{}' - position {} ~> {}", key, s, e)
-          ));
-        }
-      }
-      &Token::Text(s, e) => {
-        if operator == false {
-          value.push(source[s..e].to_string());
-          operator = true;
-        } else {
-          return Err(create_internal_error!(
-            "Operator missing between symbol or text",
-            format!(
-              "error on text : '{}' - position {} ~> {}",
-              source[s..e].to_string(),
-              s,
-              e
-            )
-          ));
-        }
-      }
-      Token::Plus => {
-        if operator == true {
-          operator = false;
-        } else {
-          return Err(create_internal_error!(
-            "Symbol or text missing before operator"
-          ));
-        }
+      Token::Exclamation if constant.is_some() => {
+        return Err(create_internal_error!(
+          "A constant can't have an optional part ('global' or 'if')"
+        ))
       }
       Token::Exclamation => {
-        if operator == true {
-          if_part = true;
-          break;
-        } else {
-          return Err(create_internal_error!(
-            "An 'if' part cannot directly follow an operator"
-          ));
-        }
-      }
-      t => {
-        return Err(create_internal_error!(format!(
-          "Found '{}' in second part (must be Token::Symbol)",
-          t
-        )))
+        if_part = true;
+        break;
       }
+      t => value_tokens.push(t),
     }
   }
+  if value_tokens.is_empty() {
+    return Err(create_internal_error!("The second part cannot be empty"));
+  }
+  let value = evaluate(source, &value_tokens, env)?;
   let mut empty: bool = true;
+  let mut global: bool = false;
+  let mut condition: bool = false;
   if if_part {
     loop {
       let token = match iter_tokens.next() {
         Some(t) => t,
+        None if global => break,
         None => {
           return Err(create_internal_error!(
             "An 'if' part can't be empty (first token)"
@@ -153,10 +93,13 @@ pub fn resolve_unit<'a>(
         &Token::Symbol(s, e) => {
           let s: &str = &source[s..e];
           if s == "if" {
+            condition = true;
             break;
+          } else if s == "global" && !global {
+            global = true;
           } else {
             return Err(create_internal_error!(format!(
-              "Found '{}' in 'if' part of statement (must be Token::Symbol['if'])",
+              "Found '{}' in optional part of statement (must be Token::Symbol['global' or 'if'])",
               s
             )));
           }
@@ -169,40 +112,42 @@ pub fn resolve_unit<'a>(
         }
       }
     }
-    loop {
-      let token = match iter_tokens.next() {
-        Some(t) => t,
-        None => {
-          return Err(create_internal_error!(
-            "An 'if' part can't be empty (second token)"
-          ))
-        }
-      };
-      match token {
-        Token::Space(_) => (),
-        &Token::Symbol(s, e) => match &source[s..e] {
-          "unset" => break,
-          "setted" => {
-            empty = false;
-            break;
+    if condition {
+      loop {
+        let token = match iter_tokens.next() {
+          Some(t) => t,
+          None => {
+            return Err(create_internal_error!(
+              "An 'if' part can't be empty (second token)"
+            ))
           }
-          s => {
+        };
+        match token {
+          Token::Space(_) => (),
+          &Token::Symbol(s, e) => match &source[s..e] {
+            "unset" => break,
+            "setted" => {
+              empty = false;
+              break;
+            }
+            s => {
+              return Err(create_internal_error!(format!(
+                "Found '{}' in 'if' part of statement (must be Token::Symbol['unset' or 'setted'])",
+                s
+              )))
+            }
+          },
+          t => {
             return Err(create_internal_error!(format!(
-              "Found '{}' in 'if' part of statement (must be Token::Symbol['unset' or 'setted'])",
-              s
-            )))
+              "Found '{}' in 'if' part of statement (must be Token::Symbol['if'])",
+              t
+            )));
           }
-        },
-        t => {
-          return Err(create_internal_error!(format!(
-            "Found '{}' in 'if' part of statement (must be Token::Symbol['if'])",
-            t
-          )));
         }
       }
     }
   }
-  let setting: bool = if if_part {
+  let setting: bool = if condition {
     let exists = match env.get(&key) {
       Ok(Some(_)) => true,
       Ok(None) => false,
@@ -225,7 +170,14 @@ pub fn resolve_unit<'a>(
     true
   };
   if setting {
-    match env.set(key, value.into_iter().collect::<String>()) {
+    let result = if let Some(origin) = constant {
+      env.set_constant(key, value, origin)
+    } else if global {
+      env.set_global(key, value)
+    } else {
+      env.set(key, value)
+    };
+    match result {
       Some(err) => return Err(create_internal_error!(err)),
       None => (),
     }
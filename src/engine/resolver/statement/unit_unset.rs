@@ -0,0 +1,70 @@
+use core::iter::Peekable;
+use core::slice::Iter;
+
+use crate::create_internal_error;
+use crate::engine::resolver::statement::Token;
+use crate::engine::Environment;
+use crate::utils::error::InternalError;
+
+fn next_token<'t>(iter_tokens: &mut Peekable<Iter<'t, Token>>) -> Option<&'t Token> {
+  loop {
+    match iter_tokens.next() {
+      Some(Token::Space(_)) => (),
+      t => return t,
+    }
+  }
+}
+
+pub fn resolve_unit<'a>(
+  env: &mut Environment,
+  source: &'a str,
+  iter_tokens: &mut Peekable<Iter<'_, Token>>,
+) -> Result<(), InternalError> {
+  let name: String = match next_token(iter_tokens) {
+    Some(&Token::Symbol(s, e)) => source[s..e].to_string(),
+    Some(t) => {
+      return Err(create_internal_error!(format!(
+        "Found '{}' in first part (must be Token::Symbol)",
+        t
+      )))
+    }
+    None => return Err(create_internal_error!("Statement can't be empty")),
+  };
+  match next_token(iter_tokens) {
+    None => {
+      if let Err(err) = env.unset(&name) {
+        return Err(create_internal_error!(err));
+      }
+    }
+    Some(Token::Text(_, _, text)) if name == "block" => {
+      env.unset_block(text);
+    }
+    Some(&Token::Symbol(s, e)) if name == "block" => {
+      let key = source[s..e].to_string();
+      let block_name = match env.get(&key) {
+        Ok(Some(v)) => v.clone(),
+        Ok(None) => {
+          return Err(create_internal_error!(format!(
+            "Undefined variable '{}' during retrieving the block name",
+            key
+          )))
+        }
+        Err(err) => return Err(create_internal_error!(err)),
+      };
+      env.unset_block(&block_name);
+    }
+    Some(t) => {
+      return Err(create_internal_error!(format!(
+        "Found '{}' after the name (only one variable or block can be removed)",
+        t
+      )))
+    }
+  }
+  match next_token(iter_tokens) {
+    None => Ok(()),
+    Some(t) => Err(create_internal_error!(format!(
+      "Found '{}' after the block name (only one block can be removed)",
+      t
+    ))),
+  }
+}
@@ -0,0 +1,162 @@
+use core::iter::Peekable;
+use core::slice::Iter;
+
+use crate::create_internal_error;
+use crate::engine::document::Node;
+use crate::engine::extensions::Value;
+use crate::engine::resolver::resolve_nodes;
+use crate::engine::resolver::statement::is_pending;
+use crate::engine::resolver::statement::Token;
+use crate::engine::resolver::Part;
+use crate::engine::Document;
+use crate::engine::Environment;
+use crate::utils::error::InternalError;
+use crate::utils::json;
+
+pub fn resolve_unit<'a>(
+  doc: &'a Document,
+  children: &[Node],
+  env: &mut Environment,
+  source: &'a str,
+  iter_tokens: &mut Peekable<Iter<'_, Token>>,
+) -> Result<Vec<Part>, InternalError> {
+  let mut variables: Vec<(String, Value)> = vec![];
+  loop {
+    let key: String = loop {
+      match iter_tokens.next() {
+        Some(Token::Space(_)) => (),
+        Some(&Token::Symbol(s, e)) => break source[s..e].to_string(),
+        Some(t) => {
+          return Err(create_internal_error!(format!(
+            "Found '{}' as variable name (must be Token::Symbol)",
+            t
+          )))
+        }
+        None => break "".to_string(),
+      }
+    };
+    if key.is_empty() {
+      break;
+    }
+    loop {
+      match iter_tokens.next() {
+        Some(Token::Space(_)) => (),
+        Some(Token::Equal) => break,
+        Some(t) => {
+          return Err(create_internal_error!(
+            "Incorrect token after the variable name (must be Token::Equal)",
+            format!("token found: {})", t)
+          ))
+        }
+        None => {
+          return Err(create_internal_error!(format!(
+            "Statement must be complete (no value for '{}', premature end)",
+            key
+          )))
+        }
+      }
+    }
+    let value: Value = loop {
+      match iter_tokens.next() {
+        Some(Token::Space(_)) => (),
+        Some(Token::Text(_, _, text)) => break Value::Text(text.clone()),
+        Some(&Token::Symbol(s, e)) if &source[s..e] == "json" && is_text_ahead(iter_tokens) => {
+          while let Some(Token::Space(_)) = iter_tokens.peek() {
+            iter_tokens.next();
+          }
+          if let Some(Token::Text(_, _, text)) = iter_tokens.next() {
+            match json::parse(text) {
+              Ok(v) => break v,
+              Err(err) => {
+                return Err(create_internal_error!(format!(
+                  "Invalid JSON value of '{}' : {}",
+                  key, err
+                )))
+              }
+            }
+          }
+        }
+        Some(&Token::Symbol(s, e)) => {
+          let symbol = source[s..e].to_string();
+          match env.get_value(&symbol) {
+            Ok(Some(v)) => break v.clone(),
+            Ok(None) => {
+              return Err(create_internal_error!(format!(
+                "Undefined variable '{}' as value of '{}'",
+                symbol, key
+              )))
+            }
+            Err(err) => return Err(create_internal_error!(err)),
+          }
+        }
+        Some(t) => {
+          return Err(create_internal_error!(format!(
+            "Found '{}' as value of '{}' (must be Token::Text or Token::Symbol)",
+            t, key
+          )))
+        }
+        None => {
+          return Err(create_internal_error!(format!(
+            "Statement must be complete (no value for '{}', premature end)",
+            key
+          )))
+        }
+      }
+    };
+    variables.push((key, value));
+  }
+  env.push_scope();
+  for (key, value) in variables {
+    if let Some(err) = env.set(key, value) {
+      env.pop_scope();
+      return Err(create_internal_error!(err));
+    }
+  }
+  let mut changed: bool = false;
+  let body = match resolve_nodes(doc, children, env, &mut changed) {
+    Ok(b) => b,
+    Err(err) => {
+      env.pop_scope();
+      return Err(err);
+    }
+  };
+  let result = if is_pending(doc, &body) {
+    let declaration = env
+      .scope_variables()
+      .iter()
+      .map(|(key, value)| format!(" {} = {}", key, literal(value)))
+      .collect::<String>();
+    let mut result = vec![Part::GeneratedText(
+      doc.delimiters.scope(&format!("with{}", declaration)),
+    )];
+    result.extend(body);
+    result.push(Part::GeneratedText(doc.delimiters.scope("endwith")));
+    result
+  } else {
+    body
+  };
+  env.pop_scope();
+  Ok(result)
+}
+
+pub fn literal(value: &Value) -> String {
+  match value {
+    Value::Text(s) => format!("\"{}\"", escape(s)),
+    v => format!("json \"{}\"", escape(&json::to_json(v))),
+  }
+}
+
+fn is_text_ahead(iter_tokens: &Peekable<Iter<'_, Token>>) -> bool {
+  let mut ahead = iter_tokens.clone();
+  loop {
+    match ahead.next() {
+      Some(Token::Space(_)) => (),
+      Some(Token::Text(_, _, _)) => return true,
+      _ => return false,
+    }
+  }
+}
+
+pub fn escape(value: &str) -> String {
+  value.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -0,0 +1,54 @@
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use crate::display_debug;
+use crate::display_debug_block;
+use crate::engine::Renderer;
+use crate::utils::conf::Configuration;
+use crate::utils::error::InternalError;
+
+pub fn watch<F>(conf: &Configuration, input: &Path, mut on_render: F)
+where
+  F: FnMut(Result<String, InternalError>),
+{
+  let interval = Duration::from_millis(conf.watch_interval);
+  let mut renderings: usize = 0;
+  let mut watched: Vec<PathBuf> = vec![input.to_path_buf()];
+  loop {
+    // taken before the rendering : a file saved during it is rendered again
+    let before = modifications(&watched);
+    let mut renderer = Renderer::from_conf(conf);
+    let result = renderer.render_path(input);
+    let mut dependencies: Vec<PathBuf> = vec![input.to_path_buf()];
+    dependencies.extend(renderer.env.dependencies().iter().map(PathBuf::from));
+    let snapshot: Vec<Option<SystemTime>> = dependencies
+      .iter()
+      .map(|path| match watched.iter().position(|p| p == path) {
+        Some(i) => before[i],
+        None => modification(path),
+      })
+      .collect();
+    watched = dependencies;
+    display_debug_block!(conf, "Watching", "{:?}", watched);
+    on_render(result);
+    renderings += 1;
+    if conf.watch_limit.is_some_and(|limit| renderings >= limit) {
+      return;
+    }
+    while modifications(&watched) == snapshot {
+      thread::sleep(interval);
+    }
+  }
+}
+
+fn modifications(paths: &[PathBuf]) -> Vec<Option<SystemTime>> {
+  paths.iter().map(|path| modification(path)).collect()
+}
+
+fn modification(path: &Path) -> Option<SystemTime> {
+  fs::metadata(path).and_then(|m| m.modified()).ok()
+}
@@ -0,0 +1,21 @@
+//! Moustache - the simple and quick text preprocessing engine.
+//!
+//! The engine can be embedded in any Rust program through [`Renderer`] :
+//!
+//! ```no_run
+//! use moustache::Configuration;
+//! use moustache::Renderer;
+//!
+//! let mut conf = Configuration::new();
+//! conf.variables.insert("name".to_string(), "world".into());
+//! let mut renderer = Renderer::from_conf(&conf);
+//! let output = renderer.render_str("hello {{ name }} !").unwrap();
+//! ```
+
+pub mod engine;
+pub mod utils;
+
+pub use crate::engine::Environment;
+pub use crate::engine::Renderer;
+pub use crate::utils::conf::Configuration;
+pub use crate::utils::error::InternalError;
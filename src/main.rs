@@ -1,11 +1,32 @@
-use std::fs::File;
+use std::fs;
 use std::io;
 use std::io::Read;
+use std::path::Path;
 
-mod engine;
-mod utils;
+use moustache::create_internal_error;
+use moustache::display_debug;
+use moustache::display_debug_block;
+use moustache::display_debug_title;
+use moustache::engine::batch;
+use moustache::engine::watch;
+use moustache::utils;
+use moustache::utils::conf::Configuration;
+use moustache::Environment;
+use moustache::InternalError;
+use moustache::Renderer;
 
-use crate::utils::error::InternalError;
+fn write(conf: &Configuration, output: &str) -> Option<String> {
+  match &conf.output {
+    Some(path) => {
+      display_debug_block!(conf, "Try to write output", "Path = {:?}", path);
+      if let Err(err) = fs::write(path, output) {
+        return Some(err.to_string());
+      }
+    }
+    None => print!("{}", output),
+  }
+  None
+}
 
 fn main() {
   let conf = match utils::args::parse() {
@@ -40,112 +61,130 @@ fn main() {
     "{}",
     conf.display()
   );
+  if conf.display_env {
+    display_debug_block!(
+      conf,
+      "Process environment exposed",
+      "{}",
+      Environment::from_args(&conf).display_process()
+    );
+  }
 
-  let mut env = engine::Environment::from_args(&conf);
-
-  let mut buffer = String::new();
-  match conf.input {
-    Some(ref path) => match File::open(path) {
-      Ok(mut fd) => match fd.read_to_string(&mut buffer) {
-        Ok(_) => (),
-        Err(err) => {
-          create_internal_error!(
-            "A fatal error occurred during reading input from file descriptor",
-            format!("Error details = {}", err)
-          )
-          .display(conf.error_formatting);
-          std::process::exit(1);
-        }
-      },
-      Err(err) => {
+  if let Some(ref source) = conf.batch_source {
+    let destination = match conf.output {
+      Some(ref d) => d,
+      None => {
         create_internal_error!(
-          "A fatal error occurred during opening input",
-          format!("Error details = {}", err.to_string())
+          "The 'render' command needs an output directory",
+          "the output must be declared with --output (or -o)"
         )
-        .display(conf.error_formatting);
+        .display_as(&conf.error_format, conf.error_formatting);
         std::process::exit(1);
       }
-    },
-    None => match io::stdin().read_to_string(&mut buffer) {
-      Ok(_) => (),
+    };
+    match batch::render_directory(&conf, Path::new(source), Path::new(destination)) {
+      Ok(report) => {
+        display_debug_block!(
+          conf,
+          "Batch rendering",
+          "files rendered = {}, files copied = {}",
+          report.rendered.len(),
+          report.copied.len()
+        );
+        display_debug_title!(conf, "End of program, no errors");
+        std::process::exit(0);
+      }
       Err(err) => {
-        create_internal_error!(
-          "A fatal error occurred during reading from STDIN",
-          format!("Error details = {}", err.to_string())
-        )
-        .display(conf.error_formatting);
+        err.display_as(&conf.error_format, conf.error_formatting);
         std::process::exit(1);
       }
-    },
-  };
-  let mut doc = engine::Document::new(&conf, buffer);
-  let mut reentrance: usize = 0;
-  loop {
-    display_debug_title!(conf, "Reentrant step n°{}", reentrance);
-    match doc.parse_parts() {
-      Ok(r) => {
-        if r {
-          let (n_parts, n_statictexts) = doc.stack_len();
-          display_debug_block!(
-            conf,
-            "Parse parts",
-            "parts found (total = {}, static text = {})",
-            n_parts,
-            n_statictexts
-          );
-        } else {
-          display_debug_block!(conf, "Parse parts", "Nothing to do");
-          break;
-        }
-      }
-      Err(mut err) => {
-        add_step_internal_error!(err, "A fatal error occurred during parsing document")
-          .display(conf.error_formatting);
+    }
+  }
+
+  if conf.is_watching {
+    let input = match conf.input {
+      Some(ref i) => i,
+      None => {
+        create_internal_error!(
+          "The watch mode needs an input file",
+          "the input must be declared with --input (or -i)"
+        )
+        .display_as(&conf.error_format, conf.error_formatting);
         std::process::exit(1);
       }
-    }
-    match doc.resolve(&mut env) {
-      Ok(changed) => {
-        if changed {
-          let _ = display_debug_block!(conf, "Resolve parts", "Document is changed");
-          doc.transform(&mut env);
-        } else {
-          if reentrance > 0 {
-            let _ = display_debug_block!(conf, "Resolve parts", "Document is not changed");
-          }
-          break;
+    };
+    watch::watch(&conf, Path::new(input), |result| match result {
+      Ok(output) => {
+        if let Some(err) = write(&conf, &output) {
+          create_internal_error!(
+            "Error during write output",
+            format!("Error details = {}", err)
+          )
+          .display_as(&conf.error_format, conf.error_formatting);
         }
       }
-      Err(mut err) => {
-        err = add_step_internal_error!(err, "Error during resolving");
-        err.display(conf.error_formatting);
+      Err(err) => err.display_as(&conf.error_format, conf.error_formatting),
+    });
+    std::process::exit(0);
+  }
+
+  let mut renderer = Renderer::from_conf(&conf);
+  let result = match conf.input {
+    Some(ref path) => renderer.render_path(path),
+    None => {
+      let mut buffer = String::new();
+      if let Err(err) = io::stdin().read_to_string(&mut buffer) {
+        create_internal_error!(
+          "A fatal error occurred during reading from STDIN",
+          format!("Error details = {}", err)
+        )
+        .display_as(&conf.error_format, conf.error_formatting);
         std::process::exit(1);
       }
+      renderer.render_str(&buffer)
     }
-    if conf.is_reentrant == false {
-      break;
+  };
+  let output = match result {
+    Ok(output) => output,
+    Err(err) => {
+      err.display_as(&conf.error_format, conf.error_formatting);
+      std::process::exit(1);
     }
-    reentrance += 1;
-  }
-
-  if doc.conf.skip_first_line {
-    doc.source = doc
-      .source
-      .split("\n")
-      .skip(1)
-      .collect::<Vec<_>>()
-      .join("\n");
-  }
+  };
 
-  if let Some(err) = doc.write() {
+  if let Some(err) = write(&conf, &output) {
     create_internal_error!(
       "Error during write output",
       format!("Error details = {}", err)
     )
-    .display(conf.error_formatting);
+    .display_as(&conf.error_format, conf.error_formatting);
     std::process::exit(1);
   }
 
+  if let Some(ref path) = conf.dump_env {
+    let dump = match renderer.dump_env() {
+      Ok(dump) => dump,
+      Err(err) => {
+        err.display_as(&conf.error_format, conf.error_formatting);
+        std::process::exit(1);
+      }
+    };
+    if path == "-" {
+      if conf.output.is_none() && !output.is_empty() && !output.ends_with('\n') {
+        println!();
+      }
+      println!("{}", dump);
+    } else if let Err(err) = fs::write(path, format!("{}\n", dump)) {
+      create_internal_error!(
+        "Error during write the dump of the environment",
+        format!("Path = {:?}", path),
+        format!("Error details = {}", err)
+      )
+      .display_as(&conf.error_format, conf.error_formatting);
+      std::process::exit(1);
+    }
+  }
+
   display_debug_title!(conf, "End of program, no errors");
   std::process::exit(0);
 }
@@ -2,30 +2,153 @@ use std::env;
 
 // use utils_macro::modifier_item;
 use crate::engine::extensions;
+use crate::engine::extensions::Value;
 use crate::utils::APP_AUTHOR;
 use crate::utils::APP_DATE;
 use crate::utils::APP_NAME;
 use crate::utils::APP_VERSION;
 
 use crate::utils::conf::Configuration;
+use crate::utils::conf::Delimiters;
+use crate::utils::conf::Escaping;
+use crate::utils::conf::UndefinedPolicy;
+use crate::utils::error::ErrorFormat;
+use crate::utils::vars_file;
+
+// arguments followed by a value (which is then read again as an argument, and
+// ignored : 'render' there is a value, not the command)
+const WITH_VALUE: [&str; 18] = [
+  "--error-format",
+  "--max-passes",
+  "--watch-interval",
+  "--watch-limit",
+  "--undefined",
+  "--autoescape",
+  "--delimiters",
+  "--input",
+  "-i",
+  "--output",
+  "-o",
+  "--include",
+  "--exclude",
+  "--var",
+  "-v",
+  "--env-prefix",
+  "--dump-env",
+  "--vars-file",
+];
 
 pub fn parse() -> Result<Configuration, String> {
   let mut c = Configuration::new();
   let mut iter = env::args().skip(1).peekable();
+  let mut previous = "".to_string();
   loop {
-    let arg = iter.next();
-    if arg.is_none() {
-      break;
-    }
-    match arg.unwrap().as_ref() {
+    let arg = match iter.next() {
+      Some(a) => a,
+      None => break,
+    };
+    match arg.as_ref() {
+      "render" if !WITH_VALUE.contains(&previous.as_str()) => match (&c.batch_source, iter.next()) {
+        (Some(_), _) => {
+          return Err("the 'render' command is declared more than once in the command line parameters".to_string())
+        }
+        (None, Some(source)) => c.batch_source = Some(source),
+        (None, None) => {
+          return Err("the 'render' command must be followed by the source directory".to_string())
+        }
+      },
       "--help" | "-h" => c.is_helping(true),
       "--help-extensions" => c.is_helping_extensions(true),
       "--debug" | "-d" => c.is_debugging(true),
       "--version" => c.display_version(true),
       "--reentrant" | "-r" => c.is_reentrant(true),
+      "--watch" | "-w" => c.is_watching(true),
       "--no-extensions" => c.no_extensions(true),
       "--error-formatting" => c.error_formatting(true),
       "--skip-first-line" => c.skip_first_line(true),
+      "--trim-blocks" => c.trim_blocks(true),
+      "--lstrip-blocks" => c.lstrip_blocks(true),
+      "--error-format" => match iter.peek() {
+        Some(next_argument) => match next_argument.as_ref() {
+          "text" => c.error_format(ErrorFormat::Text),
+          "json" => c.error_format(ErrorFormat::Json),
+          o => {
+            return Err(format!(
+              "the error format '{}' is invalid (must be 'text' or 'json')",
+              o
+            ))
+          }
+        },
+        None => {
+          return Err("the error format has been declared but not defined in the command line parameters".to_string())
+        }
+      },
+      "--max-passes" => match iter.peek() {
+        Some(next_argument) => match next_argument.parse::<usize>() {
+          Ok(n) if n > 0 => c.max_passes(n),
+          _ => {
+            return Err(format!(
+              "the maximum number of passes '{}' is invalid (must be a positive integer)",
+              next_argument
+            ))
+          }
+        },
+        None => {
+          return Err("the maximum number of passes has been declared but not defined in the command line parameters".to_string())
+        }
+      },
+      "--watch-interval" => match iter.peek() {
+        Some(next_argument) => match next_argument.parse::<u64>() {
+          Ok(n) if n > 0 => c.watch_interval(n),
+          _ => {
+            return Err(format!(
+              "the watch interval '{}' is invalid (must be a positive number of milliseconds)",
+              next_argument
+            ))
+          }
+        },
+        None => {
+          return Err(
+            "the watch interval has been declared but not defined in the command line parameters".to_string(),
+          )
+        }
+      },
+      "--watch-limit" => match iter.peek() {
+        Some(next_argument) => match next_argument.parse::<usize>() {
+          Ok(n) if n > 0 => c.watch_limit(Some(n)),
+          _ => {
+            return Err(format!(
+              "the watch limit '{}' is invalid (must be a positive integer)",
+              next_argument
+            ))
+          }
+        },
+        None => {
+          return Err(
+            "the watch limit has been declared but not defined in the command line parameters".to_string(),
+          )
+        }
+      },
+      "--undefined" => match iter.peek() {
+        Some(next_argument) => c.undefined(UndefinedPolicy::parse(next_argument)?),
+        None => {
+          return Err("the policy for undefined variables has been declared but not defined in the command line parameters".to_string())
+        }
+      },
+      a if a.starts_with("--undefined=") => c.undefined(UndefinedPolicy::parse(&a["--undefined=".len()..])?),
+      "--autoescape" => match iter.peek() {
+        Some(next_argument) => c.autoescape(Escaping::parse(next_argument)?),
+        None => {
+          return Err("the escaping has been declared but not defined in the command line parameters".to_string())
+        }
+      },
+      a if a.starts_with("--autoescape=") => c.autoescape(Escaping::parse(&a["--autoescape=".len()..])?),
+      "--delimiters" => match iter.peek() {
+        Some(next_argument) => c.delimiters(Delimiters::parse(next_argument)?),
+        None => {
+          return Err("the delimiters have been declared but not defined in the command line parameters".to_string())
+        }
+      },
       "--input" | "-i" => match iter.peek() {
         Some(next_argument) => c.input = Some(next_argument.to_string()),
         None => {
@@ -42,10 +165,22 @@ pub fn parse() -> Result<Configuration, String> {
           ))
         }
       },
+      "--include" => match iter.peek() {
+        Some(next_argument) => c.includes.push(next_argument.to_string()),
+        None => {
+          return Err("the include pattern has been declared but not defined in the command line parameters".to_string())
+        }
+      },
+      "--exclude" => match iter.peek() {
+        Some(next_argument) => c.excludes.push(next_argument.to_string()),
+        None => {
+          return Err("the exclude pattern has been declared but not defined in the command line parameters".to_string())
+        }
+      },
       "--var" | "-v" => match iter.peek() {
         Some(next_argument) => {
           if let Some((k, v)) = next_argument.split_once('=') {
-            c.variables.insert(k.to_string(), v.to_string());
+            c.variables.insert(k.to_string(), Value::Text(v.to_string()));
           } else {
             return Err(format!(
               "the value of variable in the command line parameters is invalid"
@@ -58,8 +193,42 @@ pub fn parse() -> Result<Configuration, String> {
           ))
         }
       },
+      "--env" => c.env_prefix(Some("".to_string())),
+      "--env-prefix" => match iter.peek() {
+        Some(next_argument) => c.env_prefix(Some(next_argument.to_string())),
+        None => {
+          return Err(
+            "the prefix of the environment has been declared but not defined in the command line parameters".to_string(),
+          )
+        }
+      },
+      a if a.starts_with("--env=") => c.env_prefix(Some(a["--env=".len()..].to_string())),
+      "--debug-env" => c.display_env(true),
+      "--lock-vars" => c.lock_vars(true),
+      "--dump-env" => match iter.peek() {
+        Some(next_argument) => c.dump_env(Some(next_argument.to_string())),
+        None => {
+          return Err("the dump of the environment has been declared but not defined in the command line parameters".to_string())
+        }
+      },
+      "--vars-file" => match iter.peek() {
+        Some(next_argument) => {
+          for (k, v) in vars_file::load(next_argument)? {
+            match c.variables.get_mut(&k) {
+              Some(existing) => vars_file::merge(existing, v),
+              None => {
+                c.variables.insert(k, v);
+              }
+            }
+          }
+        }
+        None => {
+          return Err("the variables file has been declared but not defined in the command line parameters".to_string())
+        }
+      },
       _ => (),
     }
+    previous = arg;
   }
   Ok(c)
 }
@@ -78,16 +247,54 @@ by {}
       "
   --help      | -h    display this message and exit (0)
   --debug     | -d    display the debug
+  --debug-env         display the process environment exposed in the debug
+  --dump-env +        write the variables and the blocks (rendered as a
+                      call) as JSON (path, or '-' for stdout) after the
+                      last pass - with arg
 
   --input +   | -i +  input of process (path ; else stdin) - with arg
   --output +  | -o +  output of process (path) - with arg
   --var +     | -v +  add var to env - with arg 
+  --vars-file +       add the vars of a file to env ('.env', '.json' or
+                      '.toml' ; nested keys joined with dots) - with arg
+  --lock-vars         the vars of the command line (--var, --vars-file)
+                      are constants
+  --env               expose the variables of the process environment
+                      (read-only, as 'env.NAME')
+  --env-prefix +      as --env, only the variables beginning with the
+                      prefix (also '--env=PREFIX') - with arg
   --reentrant | -r    document is reentrant 
+  --watch     | -w    render again the input on each change of it or of
+                      the files included or found (errors don't exit)
+  --watch-interval +  milliseconds between two checks of the watched files
+                      (default : 500) - with arg
+  --watch-limit +     number of renderings before leaving the watch mode
+                      (default : none) - with arg
+  --max-passes +      maximum number of passes of a reentrant document
+                      (default : 100) - with arg
   --error-formatting  support of ANSI color and style codes 
+  --error-format +    format of errors on stderr ('text' or 'json') - with arg
+  --undefined +       what an expression or a statement ('if', 'for',
+                      'include', 'call') with an undefined variable gives :
+                      'error' (default), 'empty' or 'keep' (left as
+                      written) - with arg
+  --autoescape +      escaping of the output of the expressions : 'html',
+                      'xml', 'shell', 'json' or 'none' (default) - with arg
+  --trim-blocks       removes the first newline after a statement or a comment
+  --lstrip-blocks     removes spaces and tabs from the start of a line
+                      up to a statement or a comment
+  --delimiters +      delimiters of expression, statement and comment
+                      (default : '{{{{ }}}} {{% %}} {{# #}}') - with arg
   --skip-first-line   removes the first line of the output, 
                       for example in the case where the call is made 
                       via a shebang of the source file
 
+  render + -o +       render each template of a directory into the output
+                      directory (same tree ; other files are copied)
+  --include +         pattern of the templates in 'render' (default : all
+                      files ; '*', '**' and '?' allowed) - with arg
+  --exclude +         pattern of the files not rendered in 'render' - with arg
+
   --help-extensions   display extensions documentation and exit (0)
   --no-extensions     disable extensions (with error)
 "
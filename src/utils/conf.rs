@@ -1,18 +1,45 @@
 use std::collections::HashMap;
 
+use crate::engine::extensions::Value;
+use crate::utils::error::ErrorFormat;
+use crate::utils::json;
+
 #[derive(Debug)]
 pub struct Configuration {
   pub input: Option<String>,
   pub output: Option<String>,
-  pub variables: HashMap<String, String>,
+  pub batch_source: Option<String>,
+  pub includes: Vec<String>,
+  pub excludes: Vec<String>,
+  pub variables: HashMap<String, Value>,
+  pub env_prefix: Option<String>,
+  pub display_env: bool,
+  pub dump_env: Option<String>,
+  pub lock_vars: bool,
   pub is_helping: bool,
   pub is_helping_extensions: bool,
   pub is_debugging: bool,
   pub is_reentrant: bool,
+  pub is_watching: bool,
+  pub watch_interval: u64,
+  pub watch_limit: Option<usize>,
+  pub max_passes: usize,
   pub display_version: bool,
   pub no_extensions: bool,
   pub error_formatting: bool,
+  pub error_format: ErrorFormat,
   pub skip_first_line: bool,
+  pub trim_blocks: bool,
+  pub lstrip_blocks: bool,
+  pub delimiters: Delimiters,
+  pub undefined: UndefinedPolicy,
+  pub autoescape: Escaping,
+}
+
+impl Default for Configuration {
+  fn default() -> Self {
+    Configuration::new()
+  }
 }
 
 impl Configuration {
@@ -20,15 +47,32 @@ impl Configuration {
     Configuration {
       input: None,
       output: None,
+      batch_source: None,
+      includes: vec![],
+      excludes: vec![],
       variables: HashMap::new(),
+      env_prefix: None,
+      display_env: false,
+      dump_env: None,
+      lock_vars: false,
       is_helping: false,
       is_helping_extensions: false,
       is_debugging: false,
       is_reentrant: false,
+      is_watching: false,
+      watch_interval: 500,
+      watch_limit: None,
+      max_passes: 100,
       display_version: false,
       no_extensions: false,
       error_formatting: false,
+      error_format: ErrorFormat::Text,
       skip_first_line: false,
+      trim_blocks: false,
+      lstrip_blocks: false,
+      delimiters: Delimiters::new(),
+      undefined: UndefinedPolicy::Error,
+      autoescape: Escaping::None,
     }
   }
   pub fn display(&self) -> String {
@@ -36,18 +80,38 @@ impl Configuration {
       r#"Configuration:
 input:                 {:?}
 output:                {:?}
+batch_source:          {:?}
+includes:              {:?}
+excludes:              {:?}
 variables:             {}
+env_prefix:            {:?}
+display_env:           '{}'
+dump_env:              {:?}
+lock_vars:             '{}'
 
 is_helping:            '{}'
 is_helping_extensions: '{}'
 is_debugging:          '{}'
 is_reentrant:          '{}'
+is_watching:           '{}'
+watch_interval:        '{}'
+watch_limit:           {:?}
+max_passes:            '{}'
 display_version:       '{}'
 no_extensions:         '{}'
 error_formatting:      '{}'
-skip_first_line:       '{}'"#,
+error_format:          '{:?}'
+skip_first_line:       '{}'
+trim_blocks:           '{}'
+lstrip_blocks:         '{}'
+delimiters:            '{}'
+undefined:             '{:?}'
+autoescape:            '{:?}'"#,
       self.input,
       self.output,
+      self.batch_source,
+      self.includes,
+      self.excludes,
       {
         let mut s: Vec<String> = vec!["".to_string()];
         for (key, val) in self.variables.iter() {
@@ -55,16 +119,42 @@ skip_first_line:       '{}'"#,
         }
         s.join("\n")
       },
+      self.env_prefix,
+      self.display_env,
+      self.dump_env,
+      self.lock_vars,
       self.is_helping,
       self.is_helping_extensions,
       self.is_debugging,
       self.is_reentrant,
+      self.is_watching,
+      self.watch_interval,
+      self.watch_limit,
+      self.max_passes,
       self.display_version,
       self.no_extensions,
       self.error_formatting,
+      self.error_format,
       self.skip_first_line,
+      self.trim_blocks,
+      self.lstrip_blocks,
+      self.delimiters.display(),
+      self.undefined,
+      self.autoescape,
     )
   }
+  pub fn env_prefix(&mut self, v: Option<String>) {
+    self.env_prefix = v;
+  }
+  pub fn display_env(&mut self, v: bool) {
+    self.display_env = v;
+  }
+  pub fn dump_env(&mut self, v: Option<String>) {
+    self.dump_env = v;
+  }
+  pub fn lock_vars(&mut self, v: bool) {
+    self.lock_vars = v;
+  }
   pub fn is_helping(&mut self, v: bool) {
     self.is_helping = v;
   }
@@ -77,6 +167,18 @@ skip_first_line:       '{}'"#,
   pub fn is_reentrant(&mut self, v: bool) {
     self.is_reentrant = v;
   }
+  pub fn is_watching(&mut self, v: bool) {
+    self.is_watching = v;
+  }
+  pub fn watch_interval(&mut self, v: u64) {
+    self.watch_interval = v;
+  }
+  pub fn watch_limit(&mut self, v: Option<usize>) {
+    self.watch_limit = v;
+  }
+  pub fn max_passes(&mut self, v: usize) {
+    self.max_passes = v;
+  }
   pub fn display_version(&mut self, v: bool) {
     self.display_version = v;
   }
@@ -86,7 +188,176 @@ skip_first_line:       '{}'"#,
   pub fn error_formatting(&mut self, v: bool) {
     self.error_formatting = v;
   }
+  pub fn error_format(&mut self, v: ErrorFormat) {
+    self.error_format = v;
+  }
   pub fn skip_first_line(&mut self, v: bool) {
     self.skip_first_line = v;
   }
+  pub fn trim_blocks(&mut self, v: bool) {
+    self.trim_blocks = v;
+  }
+  pub fn lstrip_blocks(&mut self, v: bool) {
+    self.lstrip_blocks = v;
+  }
+  pub fn undefined(&mut self, v: UndefinedPolicy) {
+    self.undefined = v;
+  }
+  pub fn autoescape(&mut self, v: Escaping) {
+    self.autoescape = v;
+  }
+  pub fn delimiters(&mut self, v: Delimiters) {
+    self.delimiters = v;
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum UndefinedPolicy {
+  Error,
+  Empty,
+  Keep,
+}
+
+impl UndefinedPolicy {
+  pub fn parse(value: &str) -> Result<Self, String> {
+    match value {
+      "error" => Ok(UndefinedPolicy::Error),
+      "empty" => Ok(UndefinedPolicy::Empty),
+      "keep" => Ok(UndefinedPolicy::Keep),
+      o => Err(format!(
+        "the policy for undefined variables '{}' is invalid (must be 'error', 'empty' or 'keep')",
+        o
+      )),
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Escaping {
+  None,
+  Html,
+  Xml,
+  Shell,
+  Json,
+}
+
+impl Escaping {
+  pub fn parse(value: &str) -> Result<Self, String> {
+    match value {
+      "none" => Ok(Escaping::None),
+      "html" => Ok(Escaping::Html),
+      "xml" => Ok(Escaping::Xml),
+      "shell" => Ok(Escaping::Shell),
+      "json" => Ok(Escaping::Json),
+      o => Err(format!(
+        "the escaping '{}' is invalid (must be 'html', 'xml', 'shell', 'json' or 'none')",
+        o
+      )),
+    }
+  }
+  pub fn name(&self) -> &str {
+    match self {
+      Escaping::None => "none",
+      Escaping::Html => "html",
+      Escaping::Xml => "xml",
+      Escaping::Shell => "shell",
+      Escaping::Json => "json",
+    }
+  }
+  pub fn escape(&self, text: &str) -> String {
+    match self {
+      Escaping::None => text.to_string(),
+      Escaping::Html | Escaping::Xml => {
+        let mut output = String::with_capacity(text.len());
+        for c in text.chars() {
+          match c {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '"' => output.push_str("&quot;"),
+            '\'' if *self == Escaping::Html => output.push_str("&#39;"),
+            '\'' => output.push_str("&apos;"),
+            c => output.push(c),
+          }
+        }
+        output
+      }
+      Escaping::Shell => format!("'{}'", text.replace('\'', "'\\''")),
+      Escaping::Json => {
+        let quoted = json::escape(text);
+        quoted[1..quoted.len() - 1].to_string()
+      }
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Delimiters {
+  pub expression: (String, String),
+  pub statement: (String, String),
+  pub comment: (String, String),
+}
+
+impl Default for Delimiters {
+  fn default() -> Self {
+    Delimiters::new()
+  }
+}
+
+impl Delimiters {
+  pub fn new() -> Self {
+    Delimiters {
+      expression: ("{{".to_string(), "}}".to_string()),
+      statement: ("{%".to_string(), "%}".to_string()),
+      comment: ("{#".to_string(), "#}".to_string()),
+    }
+  }
+  pub fn parse(value: &str) -> Result<Self, String> {
+    let items: Vec<&str> = value.split_whitespace().collect();
+    if items.len() != 6 {
+      return Err(format!(
+        "the delimiters '{}' are invalid (must be 3 pairs, for expression, statement and comment : e.g. '{{{{ }}}} {{% %}} {{# #}}')",
+        value
+      ));
+    }
+    let delimiters = Delimiters {
+      expression: (items[0].to_string(), items[1].to_string()),
+      statement: (items[2].to_string(), items[3].to_string()),
+      comment: (items[4].to_string(), items[5].to_string()),
+    };
+    if delimiters.expression.0 == delimiters.statement.0
+      || delimiters.expression.0 == delimiters.comment.0
+      || delimiters.statement.0 == delimiters.comment.0
+    {
+      return Err(format!(
+        "the delimiters '{}' are invalid (the three openings must be different)",
+        value
+      ));
+    }
+    Ok(delimiters)
+  }
+  pub fn display(&self) -> String {
+    format!(
+      "{} {} {} {} {} {}",
+      self.expression.0,
+      self.expression.1,
+      self.statement.0,
+      self.statement.1,
+      self.comment.0,
+      self.comment.1
+    )
+  }
+  pub fn statement(&self, content: &str) -> String {
+    format!("{} {} {}", self.statement.0, content, self.statement.1)
+  }
+  // a generated scope keeps the whitespace around it : the blocks are already
+  // trimmed by the pass which generated it
+  pub fn scope(&self, content: &str) -> String {
+    format!("{}+ {} +{}", self.statement.0, content, self.statement.1)
+  }
+  pub fn is_opened_in(&self, text: &str) -> bool {
+    text.contains(&self.expression.0)
+      || text.contains(&self.statement.0)
+      || text.contains(&self.comment.0)
+  }
 }
@@ -1,5 +1,15 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::utils::json;
 use crate::utils::APP_VERSION;
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorFormat {
+  Text,
+  Json,
+}
+
 #[derive(Debug)]
 struct InternalErrorStep {
   message: String,
@@ -8,14 +18,137 @@ struct InternalErrorStep {
   infos: Vec<String>,
 }
 
+#[derive(Debug, Clone)]
+pub struct SourceLocation {
+  pub file: Option<String>,
+  pub reentrance: usize,
+  pub line: usize,
+  pub column: usize,
+  pub length: usize,
+  pub snippet: String,
+}
+
+impl SourceLocation {
+  pub fn from_source(
+    file: Option<String>,
+    reentrance: usize,
+    source: &str,
+    start: usize,
+    end: usize,
+  ) -> Self {
+    let start = start.min(source.len());
+    let end = end.max(start).min(source.len());
+    let line_start = match source[..start].rfind('\n') {
+      Some(p) => p + 1,
+      None => 0,
+    };
+    let line_end = match source[start..].find('\n') {
+      Some(p) => start + p,
+      None => source.len(),
+    };
+    SourceLocation {
+      file,
+      reentrance,
+      line: source[..start].matches('\n').count() + 1,
+      column: source[line_start..start].chars().count() + 1,
+      length: source[start..end.min(line_end)].chars().count().max(1),
+      snippet: source[line_start..line_end]
+        .trim_end_matches('\r')
+        .to_string(),
+    }
+  }
+  pub fn to_json(&self) -> String {
+    format!(
+      "{{\"file\":{},\"reentrance\":{},\"line\":{},\"column\":{},\"length\":{},\"snippet\":{}}}",
+      match &self.file {
+        Some(file) => json::escape(file),
+        None => "null".to_string(),
+      },
+      self.reentrance,
+      self.line,
+      self.column,
+      self.length,
+      json::escape(&self.snippet)
+    )
+  }
+  // after the first pass, the position is the one of the part producing the
+  // generated text
+  pub fn position(&self) -> String {
+    let position = format!(
+      "{}:{}:{}",
+      self.file.as_deref().unwrap_or("<stdin>"),
+      self.line,
+      self.column
+    );
+    match self.reentrance {
+      0 => position,
+      n => format!("{} (pass n°{})", position, n),
+    }
+  }
+  pub fn format(&self, error_formatting: bool) -> String {
+    let mut output = String::new();
+    let number = self.line.to_string();
+    let margin = " ".repeat(number.len());
+    output.push_str(&format!("{} --> {}", margin, self.position()));
+    output.push_str(&format!(
+      "\n{} |\n{} | {}\n{} | ",
+      margin, number, self.snippet, margin
+    ));
+    output.push_str(
+      &self
+        .snippet
+        .chars()
+        .take(self.column - 1)
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect::<String>(),
+    );
+    if error_formatting {
+      output.push_str("\x1b[1m\x1b[31m");
+    }
+    output.push_str(&"^".repeat(self.length));
+    if error_formatting {
+      output.push_str("\x1b[0m");
+    }
+    output.push('\n');
+    output
+  }
+}
+
 #[derive(Debug)]
 pub struct InternalError {
   stack: Vec<InternalErrorStep>,
+  location: Option<Box<SourceLocation>>,
+  undefined: Option<String>,
+}
+
+impl Default for InternalError {
+  fn default() -> Self {
+    InternalError::new()
+  }
 }
 
 impl InternalError {
   pub fn new() -> Self {
-    InternalError { stack: vec![] }
+    InternalError {
+      stack: vec![],
+      location: None,
+      undefined: None,
+    }
+  }
+  pub fn with_undefined<T: Into<String>>(mut self, name: T) -> Self {
+    self.undefined = Some(name.into());
+    self
+  }
+  pub fn undefined(&self) -> Option<&str> {
+    self.undefined.as_deref()
+  }
+  pub fn location(&self) -> Option<&SourceLocation> {
+    self.location.as_deref()
+  }
+  pub fn set_location(&mut self, location: SourceLocation) {
+    if self.location.is_none() {
+      self.location = Some(Box::new(location));
+    }
   }
   pub fn add_step<T: Into<String>>(
     &mut self,
@@ -31,61 +164,104 @@ impl InternalError {
       infos,
     });
   }
-  pub fn display(&mut self, error_formatting: bool) {
-    eprint!("\n-- ");
+  pub fn format(&self, error_formatting: bool) -> String {
+    let mut output = String::new();
+    output.push_str("\n-- ");
     if error_formatting {
-      eprint!("\x1b[5m\x1b[1m\x1b[31m");
+      output.push_str("\x1b[5m\x1b[1m\x1b[31m");
     }
-    eprint!("ERROR FOUND");
+    output.push_str("ERROR FOUND");
     if error_formatting {
-      eprint!("\x1b[0m");
+      output.push_str("\x1b[0m");
     }
-    eprintln!("\n");
-    let mut deep = 0;
-    while let Some(InternalErrorStep {
-      message,
-      file,
-      line,
-      mut infos,
-    }) = self.stack.pop()
-    {
-      eprint!("[{}] >> ", deep);
+    output.push_str("\n\n");
+    if let Some(location) = &self.location {
+      output.push_str(&location.format(error_formatting));
+      output.push('\n');
+    }
+    for (deep, step) in self.stack.iter().rev().enumerate() {
+      output.push_str(&format!("[{}] >> ", deep));
       if error_formatting {
-        eprint!("\x1b[93m");
+        output.push_str("\x1b[93m");
       }
-      eprint!("{}", message);
+      output.push_str(&step.message);
       if error_formatting {
-        eprint!("\x1b[0m");
+        output.push_str("\x1b[0m");
       }
-      eprint!("\n       ");
+      output.push_str("\n       ");
       if error_formatting {
-        eprint!("\x1b[38;5;244m(");
+        output.push_str("\x1b[38;5;244m(");
       }
-      eprint!("{}/{}#{})", APP_VERSION, file, line);
+      output.push_str(&format!("{}/{}#{})", APP_VERSION, step.file, step.line));
       if error_formatting {
-        eprint!("\x1b[0m");
+        output.push_str("\x1b[0m");
       }
-      eprintln!();
-      if infos.len() > 0 {
-        while let Some(line) = infos.pop() {
-          eprint!("       ");
-          if error_formatting {
-            eprint!("\x1b[38;5;253m");
-          }
-          eprint!("{}", line);
-          if error_formatting {
-            eprint!("\x1b[0m");
-          }
-          eprintln!();
+      output.push('\n');
+      for line in step.infos.iter().rev() {
+        output.push_str("       ");
+        if error_formatting {
+          output.push_str("\x1b[38;5;253m");
+        }
+        output.push_str(line);
+        if error_formatting {
+          output.push_str("\x1b[0m");
         }
+        output.push('\n');
       }
-      eprintln!();
-      deep += 1;
+      output.push('\n');
+    }
+    output.push_str("--\n\n");
+    output
+  }
+  pub fn to_json(&self) -> String {
+    let steps: Vec<String> = self
+      .stack
+      .iter()
+      .rev()
+      .map(|step| {
+        format!(
+          "{{\"message\":{},\"infos\":[{}],\"file\":{},\"line\":{}}}",
+          json::escape(&step.message),
+          step
+            .infos
+            .iter()
+            .map(|info| json::escape(info))
+            .collect::<Vec<String>>()
+            .join(","),
+          json::escape(&step.file),
+          step.line
+        )
+      })
+      .collect();
+    format!(
+      "{{\"version\":{},\"location\":{},\"stack\":[{}]}}",
+      json::escape(APP_VERSION),
+      match &self.location {
+        Some(location) => location.to_json(),
+        None => "null".to_string(),
+      },
+      steps.join(",")
+    )
+  }
+  pub fn display(&self, error_formatting: bool) {
+    eprint!("{}", self.format(error_formatting));
+  }
+  pub fn display_as(&self, error_format: &ErrorFormat, error_formatting: bool) {
+    match error_format {
+      ErrorFormat::Text => self.display(error_formatting),
+      ErrorFormat::Json => eprintln!("{}", self.to_json()),
     }
-    eprintln!("--\n");
   }
 }
 
+impl fmt::Display for InternalError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", self.format(false))
+  }
+}
+
+impl Error for InternalError {}
+
 #[macro_export]
 macro_rules! add_step_internal_error {
     ($instance:expr, $content:expr) => {
@@ -96,10 +272,7 @@ macro_rules! add_step_internal_error {
     };
     ($instance:expr, $content:expr $(, $content_sup:expr)*) => {
         {
-          let mut infos: Vec<String> = vec!();
-          $(
-              infos.push($content_sup.into());
-          )*
+          let infos: Vec<String> = vec!($($content_sup.into()),*);
           $instance.add_step($content, (file!()).to_string(), line!(), infos);
           $instance
         }
@@ -118,10 +291,7 @@ macro_rules! create_internal_error {
     ($content:expr $(, $content_sup:expr)*) => {
         {
           let mut e: InternalError = InternalError::new();
-          let mut infos: Vec<String> = vec!();
-          $(
-              infos.push($content_sup.into());
-          )*
+          let infos: Vec<String> = vec!($($content_sup.into()),*);
           e.add_step($content, (file!()).to_string(), line!(), infos);
           e
         }
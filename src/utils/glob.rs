@@ -0,0 +1,43 @@
+// '*' stops at '/', '**' does not ; a pattern without '/' is matched against the file name
+pub fn matches(pattern: &str, path: &str) -> bool {
+  let target = if pattern.contains('/') {
+    path
+  } else {
+    path.rsplit('/').next().unwrap_or(path)
+  };
+  let pattern: Vec<char> = pattern.chars().collect();
+  let target: Vec<char> = target.chars().collect();
+  matches_from(&pattern, &target)
+}
+
+fn matches_from(pattern: &[char], target: &[char]) -> bool {
+  match pattern.first() {
+    None => target.is_empty(),
+    Some('*') if pattern.get(1) == Some(&'*') => {
+      match pattern.get(2) {
+        // '**/' : zero or more whole directories
+        Some('/') => (0..=target.len())
+          .filter(|i| *i == 0 || target[i - 1] == '/')
+          .any(|i| matches_from(&pattern[3..], &target[i..])),
+        _ => (0..=target.len()).any(|i| matches_from(&pattern[2..], &target[i..])),
+      }
+    }
+    Some('*') => {
+      let rest = &pattern[1..];
+      for i in 0..=target.len() {
+        if matches_from(rest, &target[i..]) {
+          return true;
+        }
+        if target.get(i) == Some(&'/') {
+          break;
+        }
+      }
+      false
+    }
+    Some('?') => match target.first() {
+      Some(c) if *c != '/' => matches_from(&pattern[1..], &target[1..]),
+      _ => false,
+    },
+    Some(c) => target.first() == Some(c) && matches_from(&pattern[1..], &target[1..]),
+  }
+}
@@ -0,0 +1,256 @@
+use std::collections::BTreeMap;
+
+use crate::engine::extensions::format_number;
+use crate::engine::extensions::Value;
+
+pub fn escape(value: &str) -> String {
+  let mut output = String::with_capacity(value.len() + 2);
+  output.push('"');
+  for c in value.chars() {
+    match c {
+      '"' => output.push_str("\\\""),
+      '\\' => output.push_str("\\\\"),
+      '\n' => output.push_str("\\n"),
+      '\r' => output.push_str("\\r"),
+      '\t' => output.push_str("\\t"),
+      c if (c as u32) < 0x20 => output.push_str(&format!("\\u{:04x}", c as u32)),
+      c => output.push(c),
+    }
+  }
+  output.push('"');
+  output
+}
+
+pub fn to_json(value: &Value) -> String {
+  match value {
+    Value::Text(s) | Value::Symbol(s) => escape(s),
+    Value::Integer(n) => n.to_string(),
+    Value::Number(n) if n.is_finite() => format_number(*n),
+    Value::Number(_) | Value::Void => "null".to_string(),
+    Value::True => "true".to_string(),
+    Value::False => "false".to_string(),
+    Value::Vector(items) => format!(
+      "[{}]",
+      items.iter().map(to_json).collect::<Vec<_>>().join(",")
+    ),
+    Value::Map(entries) => format!(
+      "{{{}}}",
+      entries
+        .iter()
+        .map(|(k, v)| format!("{}:{}", escape(k), to_json(v)))
+        .collect::<Vec<_>>()
+        .join(",")
+    ),
+  }
+}
+
+pub fn to_json_pretty(value: &Value, indent: usize) -> String {
+  let margin = "  ".repeat(indent + 1);
+  let end = "  ".repeat(indent);
+  match value {
+    Value::Vector(items) if !items.is_empty() => format!(
+      "[\n{}\n{}]",
+      items
+        .iter()
+        .map(|v| format!("{}{}", margin, to_json_pretty(v, indent + 1)))
+        .collect::<Vec<_>>()
+        .join(",\n"),
+      end
+    ),
+    Value::Map(entries) if !entries.is_empty() => format!(
+      "{{\n{}\n{}}}",
+      entries
+        .iter()
+        .map(|(k, v)| format!("{}{}: {}", margin, escape(k), to_json_pretty(v, indent + 1)))
+        .collect::<Vec<_>>()
+        .join(",\n"),
+      end
+    ),
+    v => to_json(v),
+  }
+}
+
+pub struct Reader {
+  chars: Vec<char>,
+  pub position: usize,
+}
+
+impl Reader {
+  pub fn new(source: &str) -> Self {
+    Reader {
+      chars: source.chars().collect(),
+      position: 0,
+    }
+  }
+  pub fn peek(&self) -> Option<char> {
+    self.chars.get(self.position).copied()
+  }
+  pub fn next_char(&mut self) -> Option<char> {
+    let c = self.peek();
+    self.position += 1;
+    c
+  }
+  pub fn starts_with(&self, pattern: &str) -> bool {
+    pattern
+      .chars()
+      .enumerate()
+      .all(|(i, c)| self.chars.get(self.position + i) == Some(&c))
+  }
+  pub fn line(&self) -> usize {
+    self.chars[..self.position.min(self.chars.len())]
+      .iter()
+      .filter(|c| **c == '\n')
+      .count()
+      + 1
+  }
+  pub fn error(&self, message: &str) -> String {
+    format!("line {} : {}", self.line(), message)
+  }
+  pub fn expect(&mut self, c: char) -> Result<(), String> {
+    match self.next_char() {
+      Some(found) if found == c => Ok(()),
+      Some(found) => Err(self.error(&format!("'{}' expected, '{}' found", c, found))),
+      None => Err(self.error(&format!("'{}' expected, end of file found", c))),
+    }
+  }
+  pub fn skip_blanks(&mut self, lines: bool, comments: bool) {
+    while let Some(c) = self.peek() {
+      match c {
+        ' ' | '\t' | '\r' => (),
+        '\n' if lines => (),
+        '#' if comments => {
+          while !matches!(self.peek(), Some('\n') | None) {
+            self.position += 1;
+          }
+          continue;
+        }
+        _ => break,
+      }
+      self.position += 1;
+    }
+  }
+  pub fn unicode(&mut self, length: usize) -> Result<u32, String> {
+    let mut code: u32 = 0;
+    for _ in 0..length {
+      match self.next_char().and_then(|c| c.to_digit(16)) {
+        Some(d) => code = code * 16 + d,
+        None => return Err(self.error("invalid unicode escape")),
+      }
+    }
+    Ok(code)
+  }
+  pub fn escape(&mut self, content: &mut String) -> Result<(), String> {
+    let c = match self.next_char() {
+      Some('"') => '"',
+      Some('\\') => '\\',
+      Some('/') => '/',
+      Some('b') => '\u{8}',
+      Some('f') => '\u{c}',
+      Some('n') => '\n',
+      Some('r') => '\r',
+      Some('t') => '\t',
+      Some('u') => {
+        let mut code = self.unicode(4)?;
+        if (0xD800..0xDC00).contains(&code) && self.starts_with("\\u") {
+          self.position += 2;
+          let low = self.unicode(4)?;
+          code = 0x10000 + ((code - 0xD800) << 10) + (low.wrapping_sub(0xDC00) & 0x3FF);
+        }
+        char::from_u32(code).unwrap_or('\u{FFFD}')
+      }
+      Some('U') => char::from_u32(self.unicode(8)?).unwrap_or('\u{FFFD}'),
+      Some(c) => return Err(self.error(&format!("invalid escape '\\{}'", c))),
+      None => return Err(self.error("unterminated string")),
+    };
+    content.push(c);
+    Ok(())
+  }
+  pub fn quoted(&mut self) -> Result<String, String> {
+    let mut content = String::new();
+    loop {
+      match self.next_char() {
+        Some('"') => return Ok(content),
+        Some('\\') => self.escape(&mut content)?,
+        Some('\n') | None => return Err(self.error("unterminated string")),
+        Some(c) => content.push(c),
+      }
+    }
+  }
+}
+
+pub fn parse(source: &str) -> Result<Value, String> {
+  let mut reader = Reader::new(source);
+  let data = value(&mut reader)?;
+  reader.skip_blanks(true, false);
+  if let Some(c) = reader.peek() {
+    return Err(reader.error(&format!("unexpected '{}' after the root value", c)));
+  }
+  Ok(data)
+}
+
+fn value(reader: &mut Reader) -> Result<Value, String> {
+  reader.skip_blanks(true, false);
+  match reader.next_char() {
+    Some('{') => {
+      let mut entries: BTreeMap<String, Value> = BTreeMap::new();
+      reader.skip_blanks(true, false);
+      if reader.peek() == Some('}') {
+        reader.position += 1;
+        return Ok(Value::Map(entries));
+      }
+      loop {
+        reader.skip_blanks(true, false);
+        reader.expect('"')?;
+        let key = reader.quoted()?;
+        reader.skip_blanks(true, false);
+        reader.expect(':')?;
+        entries.insert(key, value(reader)?);
+        reader.skip_blanks(true, false);
+        match reader.next_char() {
+          Some(',') => (),
+          Some('}') => return Ok(Value::Map(entries)),
+          _ => return Err(reader.error("',' or '}' expected in object")),
+        }
+      }
+    }
+    Some('[') => {
+      let mut items: Vec<Value> = vec![];
+      reader.skip_blanks(true, false);
+      if reader.peek() == Some(']') {
+        reader.position += 1;
+        return Ok(Value::Vector(items));
+      }
+      loop {
+        items.push(value(reader)?);
+        reader.skip_blanks(true, false);
+        match reader.next_char() {
+          Some(',') => (),
+          Some(']') => return Ok(Value::Vector(items)),
+          _ => return Err(reader.error("',' or ']' expected in array")),
+        }
+      }
+    }
+    Some('"') => Ok(Value::Text(reader.quoted()?)),
+    Some(c) => {
+      let mut word = c.to_string();
+      while let Some(c) = reader.peek() {
+        if !c.is_ascii_alphanumeric() && !"+-.".contains(c) {
+          break;
+        }
+        word.push(c);
+        reader.position += 1;
+      }
+      match word.as_str() {
+        "true" => Ok(Value::True),
+        "false" => Ok(Value::False),
+        "null" => Ok(Value::Void),
+        w => match (w.parse::<i64>(), w.parse::<f64>()) {
+          (Ok(n), _) => Ok(Value::Integer(n)),
+          (_, Ok(n)) => Ok(Value::Number(n)),
+          _ => Err(reader.error(&format!("invalid value '{}'", w))),
+        },
+      }
+    }
+    None => Err(reader.error("value expected, end of file found")),
+  }
+}
@@ -1,6 +1,9 @@
 pub mod args;
 pub mod conf;
 pub mod error;
+pub mod glob;
+pub mod json;
+pub mod vars_file;
 
 pub static APP_NAME: &'static str = "Moustache";
 pub static APP_VERSION: &'static str = "v1.1.0";
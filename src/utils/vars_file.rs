@@ -0,0 +1,499 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::engine::extensions::Value;
+use crate::utils::json;
+use crate::utils::json::Reader;
+
+pub fn load(path: &str) -> Result<Vec<(String, Value)>, String> {
+  let source = match fs::read_to_string(path) {
+    Ok(s) => s,
+    Err(err) => {
+      return Err(format!(
+        "the variables file '{}' can't be read ({})",
+        path, err
+      ))
+    }
+  };
+  let file = Path::new(path);
+  let name = file.file_name().and_then(|n| n.to_str()).unwrap_or("");
+  let result = match file.extension().and_then(|e| e.to_str()) {
+    _ if name.starts_with(".env") => parse_dotenv(&source),
+    Some("env") => parse_dotenv(&source),
+    Some("json") => json::parse(&source).and_then(root),
+    Some("toml") => parse_toml(&source),
+    _ => {
+      return Err(format!(
+        "the format of the variables file '{}' is unknown (must be '.env', '.json' or '.toml')",
+        path
+      ))
+    }
+  };
+  match result {
+    Ok(variables) => Ok(variables),
+    Err(err) => Err(format!(
+      "the variables file '{}' is invalid : {}",
+      path, err
+    )),
+  }
+}
+
+pub fn merge(target: &mut Value, value: Value) {
+  match (target, value) {
+    (Value::Map(entries), Value::Map(others)) => {
+      for (key, other) in others {
+        match entries.get_mut(&key) {
+          Some(entry) => merge(entry, other),
+          None => {
+            entries.insert(key, other);
+          }
+        }
+      }
+    }
+    (target, value) => *target = value,
+  }
+}
+
+fn root(value: Value) -> Result<Vec<(String, Value)>, String> {
+  match value {
+    Value::Map(entries) => Ok(entries.into_iter().collect()),
+    _ => Err("the root must be an object".to_string()),
+  }
+}
+
+fn parse_dotenv(source: &str) -> Result<Vec<(String, Value)>, String> {
+  let mut variables: Vec<(String, Value)> = vec![];
+  let mut lines = source.lines().enumerate();
+  while let Some((n, line)) = lines.next() {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+      continue;
+    }
+    let line = line.strip_prefix("export ").unwrap_or(line);
+    let (key, value) = match line.split_once('=') {
+      Some((k, v)) => (k.trim(), v.trim()),
+      None => return Err(format!("line {} : no '=' found", n + 1)),
+    };
+    if key.is_empty() || key.contains(char::is_whitespace) {
+      return Err(format!("line {} : the key '{}' is invalid", n + 1, key));
+    }
+    let value = match value.chars().next() {
+      Some(quote) if quote == '"' || quote == '\'' => {
+        let mut raw = value[1..].to_string();
+        loop {
+          if let Some((content, rest)) = split_quoted(&raw, quote) {
+            let rest = rest.trim();
+            if !rest.is_empty() && !rest.starts_with('#') {
+              return Err(format!(
+                "line {} : unexpected '{}' after the value",
+                n + 1,
+                rest
+              ));
+            }
+            break content;
+          }
+          match lines.next() {
+            Some((_, next_line)) => {
+              raw.push('\n');
+              raw.push_str(next_line);
+            }
+            None => return Err(format!("line {} : unterminated quoted value", n + 1)),
+          }
+        }
+      }
+      _ => match value.find(" #") {
+        Some(position) => value[..position].trim_end().to_string(),
+        None => value.to_string(),
+      },
+    };
+    variables.push((key.to_string(), Value::Text(value)));
+  }
+  Ok(variables)
+}
+
+fn split_quoted(raw: &str, quote: char) -> Option<(String, &str)> {
+  let mut content = String::new();
+  let mut chars = raw.char_indices();
+  while let Some((i, c)) = chars.next() {
+    match c {
+      c if c == quote => return Some((content, &raw[i + 1..])),
+      '\\' if quote == '"' => match chars.next() {
+        Some((_, 'n')) => content.push('\n'),
+        Some((_, 't')) => content.push('\t'),
+        Some((_, 'r')) => content.push('\r'),
+        Some((_, c)) => content.push(c),
+        None => return None,
+      },
+      c => content.push(c),
+    }
+  }
+  None
+}
+
+fn parse_toml(source: &str) -> Result<Vec<(String, Value)>, String> {
+  let mut reader = Reader::new(source);
+  let mut root: BTreeMap<String, Value> = BTreeMap::new();
+  let mut table: Vec<String> = vec![];
+  loop {
+    reader.skip_blanks(true, true);
+    match reader.peek() {
+      None => break,
+      Some('[') => {
+        reader.position += 1;
+        let is_array = reader.peek() == Some('[');
+        if is_array {
+          reader.position += 1;
+        }
+        table = toml_key(&mut reader)?;
+        reader.expect(']')?;
+        if is_array {
+          reader.expect(']')?;
+          toml_append_table(&mut root, &table).map_err(|err| reader.error(&err))?;
+        } else {
+          toml_table(&mut root, &table).map_err(|err| reader.error(&err))?;
+        }
+      }
+      Some(_) => {
+        let mut path = table.clone();
+        path.extend(toml_key(&mut reader)?);
+        reader.expect('=')?;
+        let value = toml_value(&mut reader)?;
+        toml_insert(&mut root, &path, value).map_err(|err| reader.error(&err))?;
+      }
+    }
+    reader.skip_blanks(false, true);
+    match reader.next_char() {
+      Some('\n') | None => (),
+      Some(c) => return Err(reader.error(&format!("unexpected '{}' at the end of the line", c))),
+    }
+  }
+  Ok(root.into_iter().collect())
+}
+
+fn toml_key(reader: &mut Reader) -> Result<Vec<String>, String> {
+  let mut path: Vec<String> = vec![];
+  loop {
+    reader.skip_blanks(false, false);
+    let part = match reader.peek() {
+      Some('"') => {
+        reader.position += 1;
+        reader.quoted()?
+      }
+      Some('\'') => {
+        reader.position += 1;
+        let mut part = String::new();
+        loop {
+          match reader.next_char() {
+            Some('\'') => break part,
+            Some('\n') | None => return Err(reader.error("unterminated key")),
+            Some(c) => part.push(c),
+          }
+        }
+      }
+      _ => {
+        let mut part = String::new();
+        while let Some(c) = reader.peek() {
+          if !c.is_ascii_alphanumeric() && c != '_' && c != '-' {
+            break;
+          }
+          part.push(c);
+          reader.position += 1;
+        }
+        if part.is_empty() {
+          return Err(reader.error("key expected"));
+        }
+        part
+      }
+    };
+    path.push(part);
+    reader.skip_blanks(false, false);
+    if reader.peek() != Some('.') {
+      return Ok(path);
+    }
+    reader.position += 1;
+  }
+}
+
+fn toml_value(reader: &mut Reader) -> Result<Value, String> {
+  reader.skip_blanks(false, false);
+  if reader.starts_with("\"\"\"") || reader.starts_with("'''") {
+    let literal = reader.peek() == Some('\'');
+    reader.position += 3;
+    if reader.starts_with("\r\n") {
+      reader.position += 2;
+    } else if reader.peek() == Some('\n') {
+      reader.position += 1;
+    }
+    let ending = if literal { "'''" } else { "\"\"\"" };
+    let mut content = String::new();
+    loop {
+      if reader.starts_with(ending) {
+        reader.position += 3;
+        return Ok(Value::Text(content));
+      }
+      match reader.next_char() {
+        Some('\\') if !literal => {
+          if matches!(
+            reader.peek(),
+            Some(' ') | Some('\t') | Some('\r') | Some('\n')
+          ) {
+            reader.skip_blanks(true, false);
+          } else {
+            reader.escape(&mut content)?;
+          }
+        }
+        Some(c) => content.push(c),
+        None => return Err(reader.error("unterminated multi-line string")),
+      }
+    }
+  }
+  match reader.peek() {
+    Some('"') => {
+      reader.position += 1;
+      Ok(Value::Text(reader.quoted()?))
+    }
+    Some('\'') => {
+      reader.position += 1;
+      let mut content = String::new();
+      loop {
+        match reader.next_char() {
+          Some('\'') => return Ok(Value::Text(content)),
+          Some('\n') | None => return Err(reader.error("unterminated string")),
+          Some(c) => content.push(c),
+        }
+      }
+    }
+    Some('[') => {
+      reader.position += 1;
+      let mut items: Vec<Value> = vec![];
+      loop {
+        reader.skip_blanks(true, true);
+        if reader.peek() == Some(']') {
+          reader.position += 1;
+          return Ok(Value::Vector(items));
+        }
+        items.push(toml_value(reader)?);
+        reader.skip_blanks(true, true);
+        match reader.next_char() {
+          Some(',') => (),
+          Some(']') => return Ok(Value::Vector(items)),
+          _ => return Err(reader.error("',' or ']' expected in array")),
+        }
+      }
+    }
+    Some('{') => {
+      reader.position += 1;
+      let mut entries: BTreeMap<String, Value> = BTreeMap::new();
+      reader.skip_blanks(false, false);
+      if reader.peek() == Some('}') {
+        reader.position += 1;
+        return Ok(Value::Map(entries));
+      }
+      loop {
+        let path = toml_key(reader)?;
+        reader.expect('=')?;
+        let value = toml_value(reader)?;
+        toml_insert(&mut entries, &path, value).map_err(|err| reader.error(&err))?;
+        reader.skip_blanks(false, false);
+        match reader.next_char() {
+          Some(',') => (),
+          Some('}') => return Ok(Value::Map(entries)),
+          _ => return Err(reader.error("',' or '}' expected in inline table")),
+        }
+      }
+    }
+    _ => {
+      // dates and times are kept as written
+      let mut word = String::new();
+      while let Some(c) = reader.peek() {
+        if matches!(c, ',' | ']' | '}' | '#' | '\n') {
+          break;
+        }
+        word.push(c);
+        reader.position += 1;
+      }
+      let word = word.trim_end().to_string();
+      if word.is_empty() {
+        return Err(reader.error("value expected"));
+      }
+      match word.as_str() {
+        "true" => Ok(Value::True),
+        "false" => Ok(Value::False),
+        w if toml_date(w) => Ok(Value::Text(word)),
+        w => toml_number(w).ok_or_else(|| {
+          reader.error(&format!(
+            "invalid value '{}' (not a number, a boolean, a date or a quoted string)",
+            w
+          ))
+        }),
+      }
+    }
+  }
+}
+
+fn toml_number(word: &str) -> Option<Value> {
+  for (prefix, radix) in [("0x", 16), ("0o", 8), ("0b", 2)] {
+    if let Some(digits) = word.strip_prefix(prefix) {
+      return i64::from_str_radix(&toml_digits(digits, radix)?, radix)
+        .ok()
+        .map(Value::Integer);
+    }
+  }
+  let (sign, unsigned) = match word.strip_prefix('-') {
+    Some(w) => ("-", w),
+    None => ("", word.strip_prefix('+').unwrap_or(word)),
+  };
+  match unsigned {
+    "inf" if sign == "-" => return Some(Value::Number(f64::NEG_INFINITY)),
+    "inf" => return Some(Value::Number(f64::INFINITY)),
+    "nan" => return Some(Value::Number(f64::NAN)),
+    _ => (),
+  }
+  let (mantissa, exponent) = match unsigned.split_once(['e', 'E']) {
+    Some((m, e)) => (m, Some(e)),
+    None => (unsigned, None),
+  };
+  let (integral, fraction) = match mantissa.split_once('.') {
+    Some((i, f)) => (i, Some(f)),
+    None => (mantissa, None),
+  };
+  let integral = toml_digits(integral, 10)?;
+  if integral.len() > 1 && integral.starts_with('0') {
+    return None;
+  }
+  let mut number = format!("{}{}", sign, integral);
+  if fraction.is_none() && exponent.is_none() {
+    return number.parse::<i64>().ok().map(Value::Integer);
+  }
+  if let Some(fraction) = fraction {
+    number.push('.');
+    number.push_str(&toml_digits(fraction, 10)?);
+  }
+  if let Some(exponent) = exponent {
+    let (exponent_sign, digits) = match exponent.strip_prefix('-') {
+      Some(d) => ("-", d),
+      None => ("", exponent.strip_prefix('+').unwrap_or(exponent)),
+    };
+    number.push('e');
+    number.push_str(exponent_sign);
+    number.push_str(&toml_digits(digits, 10)?);
+  }
+  number.parse::<f64>().ok().map(Value::Number)
+}
+
+// offset date-time, local date-time, local date or local time
+fn toml_date(word: &str) -> bool {
+  match (word.get(..10), word.get(10..)) {
+    (Some(date), Some(rest)) if toml_fields(date, '-', &[4, 2, 2]) => match rest.get(..1) {
+      None => true,
+      Some("T") | Some("t") | Some(" ") => toml_time(&rest[1..], true),
+      _ => false,
+    },
+    _ => toml_time(word, false),
+  }
+}
+
+fn toml_time(time: &str, with_offset: bool) -> bool {
+  let (time, offset) = match time.find(['Z', 'z', '+', '-']) {
+    Some(i) if with_offset => time.split_at(i),
+    _ => (time, ""),
+  };
+  let (time, fraction) = match time.split_once('.') {
+    Some((t, f)) => (t, f),
+    None => (time, "0"),
+  };
+  let offset = match offset {
+    "" | "Z" | "z" => true,
+    o => toml_fields(&o[1..], ':', &[2, 2]),
+  };
+  offset
+    && toml_fields(time, ':', &[2, 2, 2])
+    && !fraction.is_empty()
+    && fraction.chars().all(|c| c.is_ascii_digit())
+}
+
+fn toml_fields(text: &str, separator: char, lengths: &[usize]) -> bool {
+  let fields: Vec<&str> = text.split(separator).collect();
+  fields.len() == lengths.len()
+    && fields
+      .iter()
+      .zip(lengths)
+      .all(|(f, n)| f.len() == *n && f.chars().all(|c| c.is_ascii_digit()))
+}
+
+fn toml_digits(digits: &str, radix: u32) -> Option<String> {
+  if digits.is_empty() || digits.starts_with('_') || digits.ends_with('_') || digits.contains("__")
+  {
+    return None;
+  }
+  if !digits.chars().all(|c| c == '_' || c.is_digit(radix)) {
+    return None;
+  }
+  Some(digits.replace('_', ""))
+}
+
+fn toml_entries<'a>(
+  mut entries: &'a mut BTreeMap<String, Value>,
+  path: &[String],
+) -> Result<&'a mut BTreeMap<String, Value>, String> {
+  for key in path {
+    entries = match entries
+      .entry(key.to_string())
+      .or_insert_with(|| Value::Map(BTreeMap::new()))
+    {
+      Value::Map(e) => e,
+      Value::Vector(items) => match items.last_mut() {
+        Some(Value::Map(e)) => e,
+        _ => return Err(format!("the key '{}' is not a table", key)),
+      },
+      _ => return Err(format!("the key '{}' is already defined", key)),
+    };
+  }
+  Ok(entries)
+}
+
+fn toml_insert(
+  root: &mut BTreeMap<String, Value>,
+  path: &[String],
+  value: Value,
+) -> Result<(), String> {
+  let (key, parents) = match path.split_last() {
+    Some(p) => p,
+    None => return Err("empty key".to_string()),
+  };
+  let entries = toml_entries(root, parents)?;
+  if entries.contains_key(key) {
+    return Err(format!("the key '{}' is already defined", path.join(".")));
+  }
+  entries.insert(key.to_string(), value);
+  Ok(())
+}
+
+fn toml_table(root: &mut BTreeMap<String, Value>, path: &[String]) -> Result<(), String> {
+  toml_entries(root, path).map(|_| ())
+}
+
+fn toml_append_table(root: &mut BTreeMap<String, Value>, path: &[String]) -> Result<(), String> {
+  let (key, parents) = match path.split_last() {
+    Some(p) => p,
+    None => return Err("empty key".to_string()),
+  };
+  let entries = toml_entries(root, parents)?;
+  match entries.get_mut(key) {
+    Some(Value::Vector(items)) => items.push(Value::Map(BTreeMap::new())),
+    Some(_) => {
+      return Err(format!(
+        "the key '{}' is not an array of tables",
+        path.join(".")
+      ))
+    }
+    None => {
+      entries.insert(
+        key.to_string(),
+        Value::Vector(vec![Value::Map(BTreeMap::new())]),
+      );
+    }
+  }
+  Ok(())
+}